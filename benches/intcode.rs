@@ -0,0 +1,78 @@
+//! Interpreted vs predecoded engine timings.  Run with `cargo bench`.
+use aoc2019::*;
+use std::{
+    fs,
+    time::{Duration, Instant},
+};
+
+// Runs of each workload per engine - the fastest one is reported
+const RUNS: usize = 5;
+
+fn time<F: FnMut()>(mut f: F) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let started = Instant::now();
+            f();
+            started.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn compare<F: Fn(Engine) -> Int>(name: &str, workload: F) {
+    let expected = workload(Engine::Interpreted);
+    assert_eq!(
+        workload(Engine::Predecoded),
+        expected,
+        "{}: engines disagree",
+        name
+    );
+    let interpreted = time(|| {
+        workload(Engine::Interpreted);
+    });
+    let predecoded = time(|| {
+        workload(Engine::Predecoded);
+    });
+    println!(
+        "{:<24} interpreted {:>9.2?}  predecoded {:>9.2?}  {:.1}x",
+        name,
+        interpreted,
+        predecoded,
+        interpreted.as_secs_f64() / predecoded.as_secs_f64()
+    );
+}
+
+fn main() {
+    let day2 = fs::read_to_string("inputs/day2.txt").unwrap();
+    // A single-threaded search, since locate_target solves this one symbolically
+    let search = Search::new().patch(1, 0..=99).patch(2, 0..=99).threads(1);
+    compare("day 2 search", |engine| {
+        let mut computer = IntcodeComputer::new(&day2, &[]).unwrap();
+        computer.set_engine(engine);
+        let found = computer
+            .search_first(&search, |m, _| m.result() == 19_690_720)
+            .unwrap();
+        100 * found[0] + found[1]
+    });
+
+    let day5 = fs::read_to_string("inputs/day5.txt").unwrap();
+    compare("day 5 diagnostics x1000", |engine| {
+        (0..1000)
+            .map(|_| {
+                let mut computer = IntcodeComputer::new(&day5, &[5]).unwrap();
+                computer.set_engine(engine);
+                computer.execute().unwrap();
+                computer.drain_outputs()[0]
+            })
+            .sum()
+    });
+
+    // Counts down from ten million, then outputs and halts
+    let countdown = "1001,10,-1,10,1005,10,0,104,7,99,10000000";
+    compare("countdown 10M", |engine| {
+        let mut computer = IntcodeComputer::new(countdown, &[]).unwrap();
+        computer.set_engine(engine);
+        computer.execute().unwrap();
+        computer.steps() as Int
+    });
+}
@@ -1,11 +1,80 @@
 use aoc2019::*;
-use std::env::args;
+use std::{env::args, fs};
 
 const DAYS_IMPLEMENTED: u32 = 7;
 
+fn load(path: &str) -> Option<Vec<Int>> {
+    match fs::read_to_string(path) {
+        Ok(program) => match parse_program(&program) {
+            Ok(tape) => Some(tape),
+            Err(e) => {
+                eprintln!("Could not parse {}: {}", path, e);
+                None
+            }
+        },
+        Err(e) => {
+            eprintln!("Could not read {}: {}", path, e);
+            None
+        }
+    }
+}
+
+fn disasm(path: Option<String>) {
+    if let Some(path) = path {
+        if let Some(tape) = load(&path) {
+            for line in disassemble(&tape) {
+                println!("{}", line);
+            }
+        }
+    } else {
+        eprintln!("Usage: aoc disasm FILE");
+    }
+}
+
+fn cfg(path: Option<String>) {
+    if let Some(path) = path {
+        if let Some(tape) = load(&path) {
+            print!("{}", control_flow_graph(&tape).to_dot());
+        }
+    } else {
+        eprintln!("Usage: aoc cfg FILE");
+    }
+}
+
+fn profile(path: Option<String>, inputs: Vec<String>) {
+    let inputs: Vec<Int> = match inputs.iter().map(|s| s.parse::<Int>()).collect() {
+        Ok(inputs) => inputs,
+        Err(e) => {
+            eprintln!("Inputs must be integers: {}", e);
+            return;
+        }
+    };
+    if let Some(path) = path {
+        if let Some(tape) = load(&path) {
+            let mut computer = IntcodeComputer::from_tape(tape, IoQueue::new(&inputs));
+            computer.enable_profiling();
+            if let Err(e) = computer.execute() {
+                eprintln!("{}", e);
+            }
+            println!("outputs: {:?}", computer.drain_outputs());
+            if let Some(profile) = computer.profile() {
+                print!("{}", profile);
+            }
+        }
+    } else {
+        eprintln!("Usage: aoc profile FILE [INPUT...]");
+    }
+}
+
 fn main() {
     if let Some(day) = args().nth(1) {
-        if let Ok(day) = day.parse::<u32>() {
+        if day == "disasm" {
+            disasm(args().nth(2));
+        } else if day == "cfg" {
+            cfg(args().nth(2));
+        } else if day == "profile" {
+            profile(args().nth(2), args().skip(3).collect());
+        } else if let Ok(day) = day.parse::<u32>() {
             if day <= DAYS_IMPLEMENTED && day > 0 {
                 println!("Day {}", day);
                 match day {
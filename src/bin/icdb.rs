@@ -0,0 +1,64 @@
+use aoc2019::*;
+use std::{
+    env::args,
+    fs,
+    io::{self, BufRead, Write},
+};
+
+fn main() {
+    let path = match args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: icdb FILE [INPUT...]");
+            return;
+        }
+    };
+    let program = match fs::read_to_string(&path) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("Could not read {}: {}", path, e);
+            return;
+        }
+    };
+    let inputs = args()
+        .skip(2)
+        .map(|s| s.parse::<Int>())
+        .collect::<Result<Vec<Int>, _>>();
+    let computer = match inputs {
+        Ok(inputs) => IntcodeComputer::new(&program, &inputs),
+        Err(e) => {
+            eprintln!("Inputs must be integers: {}", e);
+            return;
+        }
+    };
+    let mut debugger = match computer {
+        Ok(computer) => Debugger::new(computer),
+        Err(e) => {
+            eprintln!("Could not parse {}: {}", path, e);
+            return;
+        }
+    };
+
+    println!("{}", debugger.command("where").unwrap_or_default());
+    let stdin = io::stdin();
+    let mut last = String::new();
+    loop {
+        print!("(icdb) ");
+        io::stdout().flush().unwrap();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+        // An empty line repeats the previous command
+        if line.trim().is_empty() {
+            line = last.clone();
+        } else {
+            last = line.clone();
+        }
+        match debugger.command(&line) {
+            Some(reply) if reply.is_empty() => {}
+            Some(reply) => println!("{}", reply),
+            None => break,
+        }
+    }
+}
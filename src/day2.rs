@@ -2,13 +2,18 @@ use super::*;
 use crate::intcode::intcode;
 
 pub fn find_inputs(input: &str, target: Int) -> Int {
-    let mut computer = IntcodeComputer::new(input, &[]);
+    let mut computer = IntcodeComputer::new(input, &[]).unwrap();
     let (noun, verb) = computer.locate_target(target).unwrap();
     100 * noun + verb
 }
 
 pub fn run() {
-    println!("{}", intcode(&get_puzzle_string(2).unwrap(), true, &[]).0);
+    println!(
+        "{}",
+        intcode(&get_puzzle_string(2).unwrap(), true, &[])
+            .unwrap()
+            .0
+    );
     println!(
         "{}",
         find_inputs(&get_puzzle_string(2).unwrap(), 19_690_720)
@@ -22,7 +27,9 @@ mod test {
     #[test]
     fn test_solutions() {
         assert_eq!(
-            intcode::intcode(&get_puzzle_string(2).unwrap(), true, &[]).0,
+            intcode::intcode(&get_puzzle_string(2).unwrap(), true, &[])
+                .unwrap()
+                .0,
             4945026
         );
         assert_eq!(
@@ -121,10 +121,10 @@ impl Line {
     fn len(self) -> u32 {
         if self.origin.0 == self.destination.0 {
             // vertical
-            (self.origin.1 - self.destination.1).abs() as u32
+            (self.origin.1 - self.destination.1).unsigned_abs()
         } else {
             // horizontal
-            (self.origin.0 - self.destination.0).abs() as u32
+            (self.origin.0 - self.destination.0).unsigned_abs()
         }
     }
 }
@@ -225,13 +225,12 @@ impl Grid {
         let mut ret = Vec::new();
         for current_path_idx in 0..path_lines.len() {
             let current_path = &path_lines[current_path_idx];
-            for other_path_idx in 0..path_lines.len() {
+            for (other_path_idx, other_path) in path_lines.iter().enumerate() {
                 if current_path_idx == other_path_idx {
                     // Only check against other paths, not lines in same wire
                     continue;
                 } else {
                     // Check each line in path against each line in each other path
-                    let other_path = &path_lines[other_path_idx];
                     for line in current_path {
                         for other_line in other_path {
                             if let Some(intersection) = line.intersection(*other_line) {
@@ -334,6 +333,7 @@ mod test {
     use super::*;
     use pretty_assertions::assert_eq;
     #[test]
+    #[allow(clippy::useless_conversion)]
     fn test_manhattan_distance() {
         assert_eq!(
             Position::default().manhattan_distance(Position::default().into()),
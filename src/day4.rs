@@ -5,7 +5,7 @@ const PUZZLE: &str = "278384-824795";
 fn char_strs(s: &str) -> Vec<&str> {
     s.split("")
         .skip(1)
-        .take_while(|c| *c != "")
+        .take_while(|c| !c.is_empty())
         .collect::<Vec<&str>>()
 }
 
@@ -1,13 +1,21 @@
 use crate::*;
+use intcode::intcode_outputs;
+
+fn diagnostic_code(input: &str, system_id: Int) -> Int {
+    *intcode_outputs(input, &[system_id])
+        .unwrap()
+        .last()
+        .unwrap()
+}
 
 pub fn run() {
-    //intcode::intcode(&get_puzzle_string(5).unwrap(), false, vec![]);
-    intcode::intcode("3,9,8,9,10,9,4,9,99,-1,8", false, &[]);
+    println!("{}", diagnostic_code(&get_puzzle_string(5).unwrap(), 1));
+    println!("{}", diagnostic_code(&get_puzzle_string(5).unwrap(), 5));
 }
 
 #[cfg(test)]
 mod test {
-    use super::intcode::intcode;
+    use super::*;
     use pretty_assertions::assert_eq;
     const CMP_8_1: &str = "3,9,8,9,10,9,4,9,99,-1,8";
     const CMP_8_2: &str = "3,9,7,9,10,9,4,9,99,-1,8";
@@ -19,15 +27,28 @@ mod test {
     const JUMP_3: &str = "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99";
     #[test]
     fn test_compare() {
-        assert_eq!(intcode(CMP_8_1, false, &[6]).0, 0);
-        assert_eq!(intcode(CMP_8_1, false, &[8]).0, 1);
-        assert_eq!(intcode(CMP_8_2, false, &[6]).0, 0);
-        assert_eq!(intcode(CMP_8_2, false, &[8]).0, 1);
-        assert_eq!(intcode(CMP_8_3, false, &[6]).0, 0);
-        assert_eq!(intcode(CMP_8_3, false, &[8]).0, 1);
-        assert_eq!(intcode(CMP_8_4, false, &[6]).0, 0);
-        assert_eq!(intcode(CMP_8_4, false, &[8]).0, 1);
+        assert_eq!(intcode_outputs(CMP_8_1, &[6]).unwrap(), vec![0]);
+        assert_eq!(intcode_outputs(CMP_8_1, &[8]).unwrap(), vec![1]);
+        assert_eq!(intcode_outputs(CMP_8_2, &[6]).unwrap(), vec![1]);
+        assert_eq!(intcode_outputs(CMP_8_2, &[8]).unwrap(), vec![0]);
+        assert_eq!(intcode_outputs(CMP_8_3, &[6]).unwrap(), vec![0]);
+        assert_eq!(intcode_outputs(CMP_8_3, &[8]).unwrap(), vec![1]);
+        assert_eq!(intcode_outputs(CMP_8_4, &[6]).unwrap(), vec![1]);
+        assert_eq!(intcode_outputs(CMP_8_4, &[8]).unwrap(), vec![0]);
     }
     #[test]
-    fn test_jump() {}
+    fn test_jump() {
+        assert_eq!(intcode_outputs(JUMP_1, &[0]).unwrap(), vec![0]);
+        assert_eq!(intcode_outputs(JUMP_1, &[5]).unwrap(), vec![1]);
+        assert_eq!(intcode_outputs(JUMP_2, &[0]).unwrap(), vec![0]);
+        assert_eq!(intcode_outputs(JUMP_2, &[5]).unwrap(), vec![1]);
+        assert_eq!(intcode_outputs(JUMP_3, &[7]).unwrap(), vec![999]);
+        assert_eq!(intcode_outputs(JUMP_3, &[8]).unwrap(), vec![1000]);
+        assert_eq!(intcode_outputs(JUMP_3, &[9]).unwrap(), vec![1001]);
+    }
+    #[test]
+    fn test_solutions() {
+        assert_eq!(diagnostic_code(&get_puzzle_string(5).unwrap(), 1), 6731945);
+        assert_eq!(diagnostic_code(&get_puzzle_string(5).unwrap(), 5), 9571668);
+    }
 }
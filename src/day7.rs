@@ -2,8 +2,6 @@ pub fn run() {}
 
 #[cfg(test)]
 mod test {
-    use super::*;
-    use pretty_assertions::assert_eq;
     #[test]
     fn test_sample() {}
 }
@@ -1,14 +1,63 @@
+mod ascii;
+mod assembler;
+mod cell;
+mod control_flow;
+mod debugger;
+mod disassembler;
+mod engine;
+mod error;
+mod history;
+mod io_queue;
+mod limits;
+mod memory;
+mod network;
+mod parser;
+mod pipeline;
+mod profile;
+mod search;
+mod snapshot;
+mod symbolic;
+mod trace;
+mod watch;
+
+pub use ascii::{AsciiComputer, AsciiOutput};
+pub use assembler::{assemble, AsmError};
+pub use cell::{Cell, Overflow};
+pub use control_flow::{control_flow_graph, BasicBlock, ControlFlowGraph, Edge};
+pub use debugger::Debugger;
+use disassembler::decode_line;
+pub use disassembler::{disassemble, Line};
+use engine::DecodeCache;
+pub use engine::Engine;
+pub use error::{ErrorKind, IntcodeError};
+pub use history::{History, HistoryEntry};
+pub use io_queue::IoQueue;
+use limits::Budget;
+pub use limits::Limits;
+use memory::Memory;
+pub use network::{Network, NetworkError, Packet, MONITOR_ADDRESS};
+pub use parser::{parse_program, ParseError, ParseErrorKind};
+pub use pipeline::{NoSuchMachine, Outcome, Pipeline, PipelineReport};
+pub use profile::{JumpCounts, Profile};
+pub use search::Search;
+pub use snapshot::{Snapshot, StateError};
 use std::{
-    fmt,
-    io::{self, ErrorKind::*, Read, Write},
+    error::Error,
+    fmt, fs,
+    io::{self, Write},
+    ops::Range,
+    path::Path,
 };
-pub type Int = isize;
-const INT_SIZE: usize = std::mem::size_of::<Int>();
+pub use symbolic::Expression;
+pub use trace::{MemWrite, TraceRecord, Tracer};
+use watch::Watches;
+pub use watch::{Access, WatchAction, WatchCause, WatchHit, WatchKind};
+pub type Int = i64;
 
-const MAX_INPUT: Int = 99;
+const MAX_INPUT: i64 = 99;
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum OpcodeVariant {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OpcodeVariant {
     Add = 1,
     Multiply = 2,
     Input = 3,
@@ -17,324 +66,1300 @@ enum OpcodeVariant {
     JumpF = 6,
     LessThan = 7,
     Equals = 8,
+    AdjustBase = 9,
     Terminate = 99,
 }
 
 impl OpcodeVariant {
-    fn new(i: Int) -> Result<Self, io::Error> {
+    /// The operation in the last two digits of `i`, if there is one
+    pub fn new(i: i64) -> Option<Self> {
         use OpcodeVariant::*;
         match i % 100 {
-            1 => Ok(Add),
-            2 => Ok(Multiply),
-            3 => Ok(Input),
-            4 => Ok(Output),
-            5 => Ok(JumpT),
-            6 => Ok(JumpF),
-            7 => Ok(LessThan),
-            8 => Ok(Equals),
-            99 => Ok(Terminate),
-            _ => Err(io::Error::new(
-                InvalidInput,
-                format!("Unknown opcode variant {}", i),
-            )),
+            1 => Some(Add),
+            2 => Some(Multiply),
+            3 => Some(Input),
+            4 => Some(Output),
+            5 => Some(JumpT),
+            6 => Some(JumpF),
+            7 => Some(LessThan),
+            8 => Some(Equals),
+            9 => Some(AdjustBase),
+            99 => Some(Terminate),
+            _ => None,
         }
     }
-    fn instruction_len(self) -> usize {
+    pub fn instruction_len(self) -> usize {
         use OpcodeVariant::*;
         match self {
             Add | Multiply | LessThan | Equals => 4,
             JumpT | JumpF => 3,
-            Input | Output => 2,
+            Input | Output | AdjustBase => 2,
             Terminate => 1,
         }
     }
+    /// Index of the parameter this instruction writes to, if any
+    pub fn write_parameter(self) -> Option<usize> {
+        use OpcodeVariant::*;
+        match self {
+            Add | Multiply | LessThan | Equals => Some(2),
+            Input => Some(0),
+            Output | JumpT | JumpF | AdjustBase | Terminate => None,
+        }
+    }
+    /// Case-insensitive inverse of `mnemonic`
+    pub fn from_mnemonic(s: &str) -> Option<Self> {
+        use OpcodeVariant::*;
+        [
+            Add, Multiply, Input, Output, JumpT, JumpF, LessThan, Equals, AdjustBase, Terminate,
+        ]
+        .iter()
+        .copied()
+        .find(|v| v.mnemonic().eq_ignore_ascii_case(s))
+    }
+    /// Upper-cased variant name, as used in listings
+    pub fn mnemonic(self) -> &'static str {
+        use OpcodeVariant::*;
+        match self {
+            Add => "ADD",
+            Multiply => "MULTIPLY",
+            Input => "INPUT",
+            Output => "OUTPUT",
+            JumpT => "JUMPT",
+            JumpF => "JUMPF",
+            LessThan => "LESSTHAN",
+            Equals => "EQUALS",
+            AdjustBase => "ADJUSTBASE",
+            Terminate => "TERMINATE",
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum ParameterMode {
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ParameterMode {
+    #[default]
     Position = 0,
     Immediate = 1,
+    Relative = 2,
 }
 
 impl ParameterMode {
-    fn new(i: Int) -> Result<Self, io::Error> {
+    pub fn new(i: i64) -> Option<Self> {
         use ParameterMode::*;
         match i {
-            0 => Ok(Position),
-            1 => Ok(Immediate),
-            _ => Err(io::Error::new(InvalidInput, "Unknown parameter mode")),
+            0 => Some(Position),
+            1 => Some(Immediate),
+            2 => Some(Relative),
+            _ => None,
         }
     }
 }
 
-impl Default for ParameterMode {
-    fn default() -> Self {
-        ParameterMode::Position
-    }
-}
-
-#[derive(Debug, Clone, Copy, PartialEq)]
-struct Parameter {
-    value: Int,
-    mode: ParameterMode,
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Parameter<T = Int> {
+    pub value: T,
+    pub mode: ParameterMode,
 }
 
-impl Parameter {
-    fn new(value: Int, mode: Int) -> Result<Self, io::Error> {
+impl<T> Parameter<T> {
+    fn new(value: T, mode: i64) -> Result<Self, ErrorKind<T>> {
         Ok(Self {
             value,
-            mode: ParameterMode::new(mode)?,
+            mode: ParameterMode::new(mode).ok_or(ErrorKind::BadMode(mode))?,
         })
     }
 }
 
-#[derive(Debug)]
-struct Opcode {
-    variant: OpcodeVariant,
-    parameters: Vec<Parameter>,
+impl<T: Cell> fmt::Display for Parameter<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ParameterMode::*;
+        match self.mode {
+            Position => write!(f, "[{}]", self.value),
+            Immediate => write!(f, "#{}", self.value),
+            Relative if self.value < T::default() => write!(f, "[rb{}]", self.value),
+            Relative => write!(f, "[rb+{}]", self.value),
+        }
+    }
+}
+
+/// A decoded instruction
+#[derive(Debug, Clone, PartialEq)]
+pub struct Opcode<T = Int> {
+    pub variant: OpcodeVariant,
+    pub parameters: Vec<Parameter<T>>,
 }
 
-impl Opcode {
-    fn new(variant: OpcodeVariant, parameters: Vec<Parameter>) -> Self {
+impl<T: Cell> Opcode<T> {
+    fn new(variant: OpcodeVariant, parameters: Vec<Parameter<T>>) -> Self {
         Self {
             variant,
             parameters,
         }
     }
+    /// Decode the instruction at `addr`, fetching cells with `fetch`
+    pub fn decode<F: Fn(usize) -> T>(fetch: F, addr: usize) -> Result<Self, ErrorKind<T>> {
+        let decoded = engine::decode(fetch, addr)?;
+        let len = decoded.variant.instruction_len() - 1;
+        Ok(Self::new(decoded.variant, decoded.params[..len].to_vec()))
+    }
+}
+
+/// Why a call to `IntcodeComputer::run` returned
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RunStatus<T = Int> {
+    Halted,
+    NeedsInput,
+    Output(T),
+    /// The step or time limit ran out at `pc` after `steps` instructions - run again to continue
+    LimitReached {
+        pc: usize,
+        steps: u64,
+    },
+    /// A watchpoint set to pause fired - the instruction that tripped it has completed
+    Watch(WatchHit<T>),
 }
 
-pub struct IntcodeComputer {
+/// An Intcode machine whose memory cells are `T`s - `Int` unless a wider type is needed
+#[derive(Debug)]
+pub struct IntcodeComputer<T = Int> {
     current_idx: usize,
-    current_mode: ParameterMode,
-    program: String,
-    tape: Vec<Int>,
-    input_stream: Box<dyn Read>,
-    output_stream: Box<dyn Write>,
+    relative_base: T,
+    memory: Memory<T>,
+    io: IoQueue<T>,
+    limits: Limits,
+    engine: Engine,
+    overflow: Overflow,
+    cache: DecodeCache<T>,
+    steps: u64,
+    last_write: Option<MemWrite<T>>,
+    tracer: Option<Tracer>,
+    profile: Option<Profile>,
+    history: Option<History<T>>,
+    watches: Watches<T>,
+    /// A pause held back while an Output status was handed out first
+    pending_watch: Option<WatchHit<T>>,
 }
 
-impl Default for IntcodeComputer {
+impl<T: Cell> Default for IntcodeComputer<T> {
     fn default() -> Self {
         Self {
-            current_idx: usize::default(),
-            current_mode: ParameterMode::default(),
-            program: String::default(),
-            tape: Vec::default(),
-            // Maybe just store a Vec<Int>??
-            input_stream: Box::new(io::stdin()),
-            output_stream: Box::new(io::stdout()),
+            current_idx: 0,
+            relative_base: T::default(),
+            memory: Memory::default(),
+            io: IoQueue::default(),
+            limits: Limits::default(),
+            engine: Engine::default(),
+            overflow: Overflow::default(),
+            cache: DecodeCache::default(),
+            steps: 0,
+            last_write: None,
+            tracer: None,
+            profile: None,
+            history: None,
+            watches: Watches::default(),
+            pending_watch: None,
         }
     }
 }
 
 impl IntcodeComputer {
-    pub fn new(input: &str, user_inputs: &[Int]) -> Self {
+    pub fn new(input: &str, user_inputs: &[Int]) -> Result<Self, ParseError> {
+        Self::with_io(input, IoQueue::new(user_inputs))
+    }
+}
+
+impl<T: Cell> IntcodeComputer<T> {
+    /// Load a program for any cell type, e.g. `IntcodeComputer::<i128>::with_io(program, IoQueue::new(&[1]))`
+    pub fn with_io(input: &str, io: IoQueue<T>) -> Result<Self, ParseError> {
+        Ok(Self::from_tape(parse_program(input)?, io))
+    }
+    /// Load an already-parsed program
+    pub fn from_tape(tape: Vec<T>, io: IoQueue<T>) -> Self {
+        Self {
+            memory: Memory::new(tape),
+            io,
+            ..Self::default()
+        }
+    }
+    /// Build a machine that resumes from a snapshot, with no limits set
+    pub fn from_snapshot(snapshot: Snapshot<T>) -> Self {
         let mut ret = Self::default();
-        ret.program = input.to_string();
-        ret.init_tape();
-        // If pre-defined inputs were passed, convert to byte stream
-        if !user_inputs.is_empty() {
-            let mut stream = vec![];
-            for i in user_inputs {
-                let bytes = i.to_ne_bytes();
-                for b in &bytes {
-                    stream.push(*b);
-                }
+        ret.restore_owned(snapshot);
+        ret
+    }
+    /// Capture the full machine state for a later `restore`
+    pub fn snapshot(&self) -> Snapshot<T> {
+        Snapshot {
+            current_idx: self.current_idx,
+            relative_base: self.relative_base.clone(),
+            memory: self.memory.clone(),
+            io: self.io.clone(),
+            steps: self.steps,
+        }
+    }
+    /// Write the full machine state to a text file that `load_state` can resume from
+    pub fn save_state<P: AsRef<Path>>(&self, path: P) -> Result<(), StateError> {
+        fs::write(path, self.snapshot().to_text())?;
+        Ok(())
+    }
+    /// Build a machine that resumes from a file written by `save_state`, with no limits set
+    pub fn load_state<P: AsRef<Path>>(path: P) -> Result<Self, StateError> {
+        Ok(Self::from_snapshot(Snapshot::from_text(
+            &fs::read_to_string(path)?,
+        )?))
+    }
+    /// Roll the machine back to a snapshot.  Limits are left as they are.
+    pub fn restore(&mut self, snapshot: &Snapshot<T>) {
+        self.restore_owned(snapshot.clone());
+    }
+    pub fn io(&self) -> &IoQueue<T> {
+        &self.io
+    }
+    pub fn io_mut(&mut self) -> &mut IoQueue<T> {
+        &mut self.io
+    }
+    pub fn push_input(&mut self, value: T) {
+        self.io.push_input(value);
+    }
+    pub fn drain_outputs(&mut self) -> Vec<T> {
+        self.io.drain_outputs()
+    }
+    /// Write a JSON-lines record of every instruction executed from here on to `sink`
+    pub fn trace_to<W: Write + Send + 'static>(&mut self, sink: W) {
+        self.tracer = Some(Tracer::new(sink));
+    }
+    /// Stop tracing, flushing anything buffered
+    pub fn stop_trace(&mut self) -> Result<(), io::Error> {
+        match self.tracer.take() {
+            Some(mut tracer) => tracer.flush(),
+            None => Ok(()),
+        }
+    }
+    /// Start counting executions per opcode, address and jump, discarding any previous profile
+    pub fn enable_profiling(&mut self) {
+        self.profile = Some(Profile::default());
+    }
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+    /// Stop profiling, handing back what was gathered
+    pub fn take_profile(&mut self) -> Option<Profile> {
+        self.profile.take()
+    }
+    /// Remember the last `capacity` instructions so they can be stepped back over, discarding any previous history
+    pub fn enable_history(&mut self, capacity: usize) {
+        self.history = Some(History::new(capacity));
+    }
+    pub fn history(&self) -> Option<&History<T>> {
+        self.history.as_ref()
+    }
+    /// Undo up to `n` recorded instructions, returning how many were undone.
+    /// Consumed inputs go back on the queue, but outputs already handed out stay handed out.
+    pub fn step_back(&mut self, n: usize) -> usize {
+        let mut undone = 0;
+        while undone < n {
+            match self.history.as_mut().and_then(History::pop) {
+                Some(entry) => self.undo(entry),
+                None => break,
             }
-            let mut input_stream = vec![];
-            input_stream.copy_from_slice(stream.as_slice());
-            ret.input_stream = Box::new(input_stream.as_slice());
+            undone += 1;
         }
-        ret
+        self.watches.rewind(self.steps);
+        undone
+    }
+    /// Step back to just before the most recent recorded instruction that wrote `address`, returning it.
+    /// Leaves the machine alone if no instruction in the history wrote there.
+    pub fn rewind_to_write(&mut self, address: usize) -> Option<HistoryEntry<T>> {
+        let entry = self.history.as_ref()?.last_write_to(address)?.clone();
+        self.step_back((self.steps - entry.step) as usize);
+        Some(entry)
+    }
+    /// Watch reads and/or writes made by instruction parameters to `range`, returning an id for `unwatch`
+    pub fn watch(&mut self, range: Range<usize>, kind: WatchKind, action: WatchAction<T>) -> usize {
+        self.watches.add(range, kind, action)
+    }
+    pub fn unwatch(&mut self, id: usize) -> bool {
+        self.watches.remove(id)
+    }
+    /// Fire `action` on any write to a cell that has already executed as part of an instruction.
+    /// Only instructions executed from here on count.  Pass None to switch the check off.
+    pub fn detect_self_modification(&mut self, action: Option<WatchAction<T>>) {
+        self.watches.set_self_modification(action);
+    }
+    /// Choose how `run` and `execute` carry out instructions.  `step` always interprets.
+    pub fn set_engine(&mut self, engine: Engine) {
+        self.engine = engine;
+    }
+    /// Choose what Add, Multiply and AdjustBase do on overflow - Checked faults by default.
+    /// The policy is explicit, so results don't depend on the build profile.
+    pub fn set_overflow(&mut self, overflow: Overflow) {
+        self.overflow = overflow;
+    }
+    /// Cap each subsequent `run` or `execute` call
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+    pub fn pc(&self) -> usize {
+        self.current_idx
+    }
+    pub fn relative_base(&self) -> &T {
+        &self.relative_base
+    }
+    /// Read memory directly, without going through an instruction
+    pub fn peek(&self, addr: usize) -> T {
+        self.memory.cell(addr)
+    }
+    /// Patch memory directly, without going through an instruction
+    pub fn poke(&mut self, addr: usize, value: T) {
+        self.store(addr, value);
+    }
+    /// The instruction about to execute, decoded for display
+    pub fn current_line(&self) -> Line<T> {
+        decode_line(|addr| self.memory.cell(addr), self.current_idx, usize::MAX)
+    }
+    /// Total instructions executed since this machine was loaded
+    pub fn steps(&self) -> u64 {
+        self.steps
     }
     pub fn fix_1202bug(&mut self) {
-        self.enter_inputs(12, 2);
-    }
-    pub fn execute(&mut self) -> Result<(), io::Error> {
-        let mut running = true;
-        while running {
-            let mut hop = true;
-            let opcode = self.get_opcode()?;
-            use OpcodeVariant::*;
-            match opcode.variant {
-                Add => {
-                    let lhs = self.read_parameter(opcode.parameters[0]);
-                    let rhs = self.read_parameter(opcode.parameters[1]);
-                    let dest = opcode.parameters[2].value;
-                    self.set_value_at(dest as usize, lhs + rhs);
-                }
-                Multiply => {
-                    let lhs = self.read_parameter(opcode.parameters[0]);
-                    let rhs = self.read_parameter(opcode.parameters[1]);
-                    let dest = opcode.parameters[2].value;
-                    self.set_value_at(dest as usize, lhs * rhs);
-                }
-                Input => {
-                    // If there are some left, use those, otherwise wait for stdin
-                    print!("Enter value> ");
-                    self.output_stream.flush()?;
-                    // Read exactly one 8-byte number
-                    let mut dest_buffer: [u8; INT_SIZE] = [0; INT_SIZE];
-                    self.input_stream
-                        .read_exact(&mut dest_buffer)
-                        .expect("Unable to read destination from input stream");
-                    self.set_value_at(
-                        opcode.parameters[0].value as usize, // TODO destinations are weird, see line 124
-                        Int::from_ne_bytes(dest_buffer),
-                    );
-                }
-                Output => {
-                    // TODO use self.output_stream
-                    println!("{}", self.read_parameter(opcode.parameters[0]));
-                    io::stdout().flush()?;
-                }
-                JumpT => {
-                    let check_val = self.read_parameter(opcode.parameters[0]);
-                    let jmp = self.read_parameter(opcode.parameters[1]);
-                    if check_val != 0 {
-                        self.current_idx = jmp as usize;
-                        hop = false;
-                    }
+        self.enter_inputs(T::from_i64(12), T::from_i64(2));
+    }
+    /// Run to completion, buffering outputs.  Fails if the program wants input that isn't queued.
+    /// Watchpoint pauses are skipped over - use `run` to stop on them.
+    pub fn execute(&mut self) -> Result<(), IntcodeError<T>> {
+        let mut budget = Budget::new(self.limits, self.steps);
+        loop {
+            match self.run_budgeted(&mut budget)? {
+                RunStatus::Halted => return Ok(()),
+                RunStatus::Output(value) => self
+                    .io
+                    .write(value)
+                    .map_err(|e| self.fault(ErrorKind::Io(e), None))?,
+                RunStatus::NeedsInput => return Err(self.fault(ErrorKind::InputExhausted, None)),
+                RunStatus::LimitReached { steps, .. } => {
+                    return Err(self.fault(ErrorKind::LimitReached(steps), None))
                 }
-                JumpF => {
-                    let check_val = self.read_parameter(opcode.parameters[0]);
-                    let jmp = self.read_parameter(opcode.parameters[1]);
-                    if check_val == 0 {
-                        self.current_idx = jmp as usize;
-                        hop = false;
+                RunStatus::Watch(_) => {}
+            }
+        }
+    }
+    /// Run until the program halts, produces an output, or blocks on an empty input queue.
+    /// Outputs are handed back directly rather than buffered.  Call again to resume.
+    pub fn run(&mut self) -> Result<RunStatus<T>, IntcodeError<T>> {
+        let mut budget = Budget::new(self.limits, self.steps);
+        self.run_budgeted(&mut budget)
+    }
+    fn run_budgeted(&mut self, budget: &mut Budget) -> Result<RunStatus<T>, IntcodeError<T>> {
+        if self.engine == Engine::Predecoded && !self.instrumented() {
+            return self.run_predecoded(budget);
+        }
+        loop {
+            if budget.exhausted(self.steps) {
+                return Ok(RunStatus::LimitReached {
+                    pc: self.current_idx,
+                    steps: budget.spent(self.steps),
+                });
+            }
+            if let Some(status) = self.step()? {
+                return Ok(status);
+            }
+        }
+    }
+    /// Execute a single instruction, returning a status if it halted, blocked, or output a value
+    pub fn step(&mut self) -> Result<Option<RunStatus<T>>, IntcodeError<T>> {
+        let result = self.step_instruction();
+        if let (Err(e), Some(tracer)) = (&result, self.tracer.as_mut()) {
+            let record = TraceRecord {
+                step: self.steps,
+                pc: e.pc,
+                opcode: e.opcode.clone(),
+                variant: e.opcode.to_i64().and_then(OpcodeVariant::new),
+                operands: Vec::new(),
+                write: None,
+                error: Some(e.kind.to_string()),
+            };
+            // Already failing - if the sink is what broke, there's nothing more to report
+            let _ = tracer.record(&record);
+        }
+        result
+    }
+    fn step_instruction(&mut self) -> Result<Option<RunStatus<T>>, IntcodeError<T>> {
+        if let Some(hit) = self.pending_watch.take() {
+            return Ok(Some(RunStatus::Watch(hit)));
+        }
+        let mut hop = true;
+        let mut status = None;
+        let mut consumed = None;
+        let pc = self.current_idx;
+        // Only worth copying if there's a history to record it in
+        let relative_base = self.history.as_ref().map(|_| self.relative_base.clone());
+        let opcode = self.get_opcode()?;
+        // Capture the raw opcode up front in case the instruction overwrites itself
+        let traced = match self.tracer {
+            Some(_) => Some((self.memory.cell(pc), self.resolve_operands(&opcode)?)),
+            None => None,
+        };
+        let reads = if self.watches.watches_reads() {
+            self.read_accesses(&opcode)
+        } else {
+            Vec::new()
+        };
+        self.last_write = None;
+        use OpcodeVariant::*;
+        match opcode.variant {
+            Add => {
+                let lhs = self.read_parameter(&opcode.parameters[0])?;
+                let rhs = self.read_parameter(&opcode.parameters[1])?;
+                let dest = self.write_target(&opcode.parameters[2])?;
+                let value = self.arithmetic(Add, &lhs, &rhs)?;
+                self.set_value_at(dest, value);
+            }
+            Multiply => {
+                let lhs = self.read_parameter(&opcode.parameters[0])?;
+                let rhs = self.read_parameter(&opcode.parameters[1])?;
+                let dest = self.write_target(&opcode.parameters[2])?;
+                let value = self.arithmetic(Multiply, &lhs, &rhs)?;
+                self.set_value_at(dest, value);
+            }
+            Input => {
+                let input = self
+                    .io
+                    .read()
+                    .map_err(|e| self.fault(ErrorKind::Io(e), None))?;
+                if let Some(value) = input {
+                    let dest = self.write_target(&opcode.parameters[0])?;
+                    if self.history.is_some() {
+                        consumed = Some(value.clone());
                     }
+                    self.set_value_at(dest, value);
+                } else {
+                    // Stay on this instruction so it's retried on resume
+                    return Ok(Some(RunStatus::NeedsInput));
                 }
-                LessThan => {
-                    let lhs = self.read_parameter(opcode.parameters[0]);
-                    let rhs = self.read_parameter(opcode.parameters[1]);
-                    let dest = opcode.parameters[2].value;
-                    let val = if lhs < rhs { 1 } else { 0 };
-                    self.set_value_at(dest as usize, val);
+            }
+            Output => {
+                let value = self.read_parameter(&opcode.parameters[0])?;
+                status = Some(RunStatus::Output(value));
+            }
+            JumpT => {
+                let check_val = self.read_parameter(&opcode.parameters[0])?;
+                let jmp = self.read_parameter(&opcode.parameters[1])?;
+                if !check_val.is_zero() {
+                    self.current_idx = self.jump_target(&jmp, &opcode.parameters[1])?;
+                    hop = false;
                 }
-                Equals => {
-                    let lhs = self.read_parameter(opcode.parameters[0]);
-                    let rhs = self.read_parameter(opcode.parameters[1]);
-                    let dest = opcode.parameters[2].value;
-                    let val = if lhs == rhs { 1 } else { 0 };
-                    self.set_value_at(dest as usize, val);
+            }
+            JumpF => {
+                let check_val = self.read_parameter(&opcode.parameters[0])?;
+                let jmp = self.read_parameter(&opcode.parameters[1])?;
+                if check_val.is_zero() {
+                    self.current_idx = self.jump_target(&jmp, &opcode.parameters[1])?;
+                    hop = false;
                 }
-                Terminate => running = false,
             }
-            if hop {
-                self.current_idx += opcode.variant.instruction_len();
+            LessThan => {
+                let lhs = self.read_parameter(&opcode.parameters[0])?;
+                let rhs = self.read_parameter(&opcode.parameters[1])?;
+                let dest = self.write_target(&opcode.parameters[2])?;
+                let val = if lhs < rhs { 1 } else { 0 };
+                self.set_value_at(dest, T::from_i64(val));
+            }
+            Equals => {
+                let lhs = self.read_parameter(&opcode.parameters[0])?;
+                let rhs = self.read_parameter(&opcode.parameters[1])?;
+                let dest = self.write_target(&opcode.parameters[2])?;
+                let val = if lhs == rhs { 1 } else { 0 };
+                self.set_value_at(dest, T::from_i64(val));
+            }
+            AdjustBase => {
+                let offset = self.read_parameter(&opcode.parameters[0])?;
+                self.relative_base = self.arithmetic(AdjustBase, &self.relative_base, &offset)?;
+            }
+            // Stay parked on the terminator so further runs keep reporting Halted
+            Terminate => {
+                if let Some((raw, operands)) = traced {
+                    self.trace(TraceRecord {
+                        step: self.steps,
+                        pc,
+                        opcode: raw,
+                        variant: Some(Terminate),
+                        operands,
+                        write: None,
+                        error: None,
+                    })?;
+                }
+                return Ok(Some(RunStatus::Halted));
             }
         }
-        Ok(())
+        if hop {
+            self.current_idx += opcode.variant.instruction_len();
+        }
+        if let Some(profile) = self.profile.as_mut() {
+            let jump = if hop { None } else { Some(self.current_idx) };
+            profile.record(pc, opcode.variant, jump);
+        }
+        if let Some((raw, operands)) = traced {
+            self.trace(TraceRecord {
+                step: self.steps,
+                pc,
+                opcode: raw,
+                variant: Some(opcode.variant),
+                operands,
+                write: self.last_write.clone(),
+                error: None,
+            })?;
+        }
+        if let (Some(history), Some(relative_base)) = (self.history.as_mut(), relative_base) {
+            history.push(HistoryEntry {
+                step: self.steps,
+                pc,
+                relative_base,
+                write: self.last_write.clone(),
+                input: consumed,
+            });
+        }
+        if self.watches.is_active() {
+            let len = opcode.variant.instruction_len();
+            self.pending_watch =
+                self.watches
+                    .check(pc, len, self.steps, &reads, self.last_write.as_ref());
+        }
+        self.steps += 1;
+        if status.is_none() {
+            status = self.pending_watch.take().map(RunStatus::Watch);
+        }
+        Ok(status)
     }
-    pub fn locate_target(&mut self, target: Int) -> Result<(Int, Int), io::Error> {
-        for noun in 0..=MAX_INPUT {
-            for verb in 0..=MAX_INPUT {
-                self.reset();
-                self.enter_inputs(noun, verb);
+    /// The first noun and verb in 0..=99 that leave `target` in cell 0, searching in parallel
+    /// unless it can be solved symbolically
+    pub fn locate_target(&mut self, target: T) -> Result<(T, T), IntcodeError<T>> {
+        let checkpoint = self.snapshot();
+        // Plain arithmetic on the noun and verb can be solved outright, then checked with one run
+        let solved = target
+            .to_i64()
+            .and_then(|t| self.symbolic_result()?.solve(t as i128));
+        if let Some((noun, verb)) = solved {
+            let (noun, verb) = (T::from_i64(noun), T::from_i64(verb));
+            self.enter_inputs(noun.clone(), verb.clone());
+            if self.execute().is_ok() && self.result() == target {
+                return Ok((noun, verb));
+            }
+        }
+        self.restore(&checkpoint);
+        let search = Search::new()
+            .patch(1, 0..=MAX_INPUT)
+            .patch(2, 0..=MAX_INPUT);
+        match self.search_first(&search, |m, _| m.result() == target) {
+            Ok(values) => {
+                let (noun, verb) = (values[0].clone(), values[1].clone());
+                // Leave the machine as the matching run left it
+                self.enter_inputs(noun.clone(), verb.clone());
                 self.execute()?;
-                if self.result() == target {
-                    return Ok((noun, verb));
-                }
+                Ok((noun, verb))
             }
+            Err(_) => Err(self.fault(ErrorKind::TargetNotFound(target), None)),
         }
-        panic!("Tried all possible int pairs - no match");
     }
-    pub fn result(&self) -> Int {
-        self.get_value_at(0)
+    pub fn result(&self) -> T {
+        self.memory.cell(0)
     }
-    fn enter_inputs(&mut self, noun: Int, verb: Int) {
-        self.tape[1] = noun;
-        self.tape[2] = verb;
+    fn enter_inputs(&mut self, noun: T, verb: T) {
+        self.store(1, noun);
+        self.store(2, verb);
     }
-    fn get_opcode(&self) -> Result<Opcode, io::Error> {
-        // Get variant
-        let mut opcode_int = self.tape[self.current_idx];
-        let variant = OpcodeVariant::new(opcode_int)?;
-        let code_len = variant.instruction_len();
-
-        // Get parameters with modes
-        // Variant is ones place and tens place
-        opcode_int = (opcode_int as f64 / 100.0).floor() as Int;
-        // Remaining place values dictate parameter modes
-        let mut parameters = Vec::new();
-        for i in 1..code_len {
-            parameters.push(Parameter::new(
-                self.tape[self.current_idx + i],
-                opcode_int % 10,
-            )?);
-            opcode_int = (opcode_int as f64 / 10.0).floor() as Int;
+    fn trace(&mut self, record: TraceRecord<T>) -> Result<(), IntcodeError<T>> {
+        match self.tracer.as_mut().map(|tracer| tracer.record(&record)) {
+            Some(Err(e)) => Err(IntcodeError::new(
+                record.pc,
+                record.opcode,
+                None,
+                ErrorKind::Io(e),
+            )),
+            _ => Ok(()),
+        }
+    }
+    fn get_opcode(&self) -> Result<Opcode<T>, IntcodeError<T>> {
+        Opcode::decode(|addr| self.memory.cell(addr), self.current_idx)
+            .map_err(|kind| self.fault(kind, None))
+    }
+    fn fault(&self, kind: ErrorKind<T>, mode: Option<ParameterMode>) -> IntcodeError<T> {
+        IntcodeError::new(
+            self.current_idx,
+            self.memory.cell(self.current_idx),
+            mode,
+            kind,
+        )
+    }
+    /// Read every parameter of an instruction, or resolve its address if it's a destination
+    fn resolve_operands(&self, opcode: &Opcode<T>) -> Result<Vec<T>, IntcodeError<T>> {
+        let write_idx = opcode.variant.write_parameter();
+        opcode
+            .parameters
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                if Some(i) == write_idx {
+                    self.write_target(p).map(|addr| T::from_i64(addr as i64))
+                } else {
+                    self.read_parameter(p)
+                }
+            })
+            .collect()
+    }
+    /// Addresses and values an instruction's parameters are about to read
+    fn read_accesses(&self, opcode: &Opcode<T>) -> Vec<(usize, T)> {
+        let write_idx = opcode.variant.write_parameter();
+        opcode
+            .parameters
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| Some(*i) != write_idx)
+            .filter_map(|(_, p)| match p.mode {
+                ParameterMode::Position => Memory::address(&p.value).ok(),
+                ParameterMode::Relative => self.relative_address(&p.value).ok(),
+                ParameterMode::Immediate => None,
+            })
+            .map(|addr| (addr, self.memory.cell(addr)))
+            .collect()
+    }
+    /// Apply the overflow policy to an Add, Multiply or AdjustBase
+    fn arithmetic(&self, variant: OpcodeVariant, lhs: &T, rhs: &T) -> Result<T, IntcodeError<T>> {
+        match variant {
+            OpcodeVariant::Multiply => self.overflow.mul(lhs, rhs),
+            _ => self.overflow.add(lhs, rhs),
+        }
+        .ok_or_else(|| self.fault(ErrorKind::Overflow(variant, lhs.clone(), rhs.clone()), None))
+    }
+    /// A memory fault under every overflow policy if `rb + offset` doesn't fit in a cell, since
+    /// no valid address lies past either end.  The fault names the sum clamped to the cell range.
+    fn relative_address(&self, offset: &T) -> Result<usize, ErrorKind<T>> {
+        match self.relative_base.checked_add(offset) {
+            Some(addr) => Memory::address(&addr),
+            None => Err(ErrorKind::MemoryFault(
+                self.relative_base.saturating_add(offset),
+            )),
         }
-        Ok(Opcode::new(variant, parameters))
     }
-    fn get_value_at(&self, pos: usize) -> Int {
-        self.tape[pos]
+    fn jump_target(&self, jmp: &T, p: &Parameter<T>) -> Result<usize, IntcodeError<T>> {
+        Memory::address(jmp).map_err(|kind| self.fault(kind, Some(p.mode)))
     }
-    fn read_parameter(&self, p: Parameter) -> Int {
+    fn read_parameter(&self, p: &Parameter<T>) -> Result<T, IntcodeError<T>> {
         use ParameterMode::*;
         match p.mode {
-            Position => self.get_value_at(p.value as usize),
-            Immediate => p.value,
+            Position => self.memory.get(&p.value),
+            Immediate => Ok(p.value.clone()),
+            Relative => self
+                .relative_address(&p.value)
+                .map(|addr| self.memory.cell(addr)),
         }
+        .map_err(|kind| self.fault(kind, Some(p.mode)))
     }
-    fn init_tape(&mut self) {
-        self.tape = self
-            .program
-            .split(',')
-            .map(|s| s.parse::<Int>())
-            .filter(|res| res.is_ok())
-            .map(|res| res.unwrap())
-            .collect();
+    fn write_target(&self, p: &Parameter<T>) -> Result<usize, IntcodeError<T>> {
+        use ParameterMode::*;
+        match p.mode {
+            Position => Memory::address(&p.value),
+            Immediate => Err(ErrorKind::ImmediateWrite),
+            Relative => self.relative_address(&p.value),
+        }
+        .map_err(|kind| self.fault(kind, Some(p.mode)))
     }
-    fn reset(&mut self) {
-        self.init_tape();
-        self.current_idx = 0;
+    fn undo(&mut self, entry: HistoryEntry<T>) {
+        if let Some(w) = entry.write {
+            self.store(w.address, w.old);
+        }
+        if let Some(value) = entry.input {
+            self.io.unread(value);
+        }
+        self.current_idx = entry.pc;
+        self.relative_base = entry.relative_base;
+        self.steps = entry.step;
+        self.last_write = None;
+        self.pending_watch = None;
+    }
+    fn restore_owned(&mut self, snapshot: Snapshot<T>) {
+        self.cache
+            .invalidate_changes(&self.memory, &snapshot.memory);
+        // Recorded history describes the timeline being abandoned
+        if let Some(history) = self.history.as_mut() {
+            *history = History::new(history.capacity());
+        }
+        // So is anything found by watching it
+        self.watches.reset();
+        self.last_write = None;
+        self.pending_watch = None;
+        self.current_idx = snapshot.current_idx;
+        self.relative_base = snapshot.relative_base;
+        self.memory = snapshot.memory;
+        self.io = snapshot.io;
+        self.steps = snapshot.steps;
     }
-    fn set_value_at(&mut self, pos: usize, new_value: Int) {
-        self.tape[pos] = new_value;
+    fn set_value_at(&mut self, pos: usize, new_value: T) {
+        self.last_write = Some(MemWrite {
+            address: pos,
+            old: self.memory.cell(pos),
+            new: new_value.clone(),
+        });
+        self.store(pos, new_value);
+    }
+    /// Every memory write goes through here so cached decodes never go stale
+    fn store(&mut self, addr: usize, value: T) {
+        self.memory.set_cell(addr, value);
+        self.cache.invalidate(addr);
+    }
+    /// Whether anything is watching execution closely enough to need `step`
+    fn instrumented(&self) -> bool {
+        self.tracer.is_some()
+            || self.profile.is_some()
+            || self.history.is_some()
+            || self.watches.is_active()
+            || self.pending_watch.is_some()
     }
 }
 
-impl fmt::Display for IntcodeComputer {
+impl<T: Cell> fmt::Display for IntcodeComputer<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut ret = String::new();
-        for cell in &self.tape {
-            ret.push_str(&format!("{},", cell));
-        }
-        // trim last ','
-        write!(f, "{}", &ret[0..ret.len() - 1])
+        write!(f, "{}", self.memory)
     }
 }
 
-pub fn intcode(input: &str, buggy: bool, user_inputs: &[Int]) -> (Int, String) {
-    let mut computer = IntcodeComputer::new(input, user_inputs);
+/// Run a day 2 style program, returning cell 0 and the final memory
+pub fn intcode(
+    input: &str,
+    buggy: bool,
+    user_inputs: &[Int],
+) -> Result<(Int, String), Box<dyn Error>> {
+    let mut computer = IntcodeComputer::new(input, user_inputs)?;
     if buggy {
         computer.fix_1202bug();
     }
-    computer.execute().unwrap();
-    (computer.result(), computer.to_string())
+    computer.execute()?;
+    Ok((computer.result(), computer.to_string()))
+}
+
+/// Run a program to completion on `user_inputs`, returning everything it output
+pub fn intcode_outputs(input: &str, user_inputs: &[Int]) -> Result<Vec<Int>, Box<dyn Error>> {
+    let mut computer = IntcodeComputer::new(input, user_inputs)?;
+    computer.execute()?;
+    Ok(computer.drain_outputs())
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
     use pretty_assertions::assert_eq;
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
 
     #[test]
     fn test_v0_day2() {
-        assert_eq!(intcode("1,0,0,0,99", false, &[]).1, "2,0,0,0,99");
-        assert_eq!(intcode("2,3,0,3,99", false, &[]).1, "2,3,0,6,99");
-        assert_eq!(intcode("2,4,4,5,99,0", false, &[]).1, "2,4,4,5,99,9801");
+        assert_eq!(intcode("1,0,0,0,99", false, &[]).unwrap().1, "2,0,0,0,99");
+        assert_eq!(intcode("2,3,0,3,99", false, &[]).unwrap().1, "2,3,0,6,99");
+        assert_eq!(
+            intcode("2,4,4,5,99,0", false, &[]).unwrap().1,
+            "2,4,4,5,99,9801"
+        );
         assert_eq!(
-            intcode("1,1,1,4,99,5,6,0,99", false, &[]).1,
+            intcode("1,1,1,4,99,5,6,0,99", false, &[]).unwrap().1,
             "30,1,1,4,2,5,6,0,99"
         );
         assert_eq!(
-            intcode("1,9,10,3,2,3,11,0,99,30,40,50", false, &[]).1,
+            intcode("1,9,10,3,2,3,11,0,99,30,40,50", false, &[])
+                .unwrap()
+                .1,
             "3500,9,10,70,2,3,11,0,99,30,40,50"
         );
     }
     #[test]
     fn test_v1_day5() {
-        assert_eq!(intcode("1002,4,3,4,33", false, &[]).1, "1002,4,3,4,99");
-        assert_eq!(intcode("1101,100,-1,4,0", false, &[]).1, "1101,100,-1,4,99");
+        assert_eq!(
+            intcode("1002,4,3,4,33", false, &[]).unwrap().1,
+            "1002,4,3,4,99"
+        );
+        assert_eq!(
+            intcode("1101,100,-1,4,0", false, &[]).unwrap().1,
+            "1101,100,-1,4,99"
+        );
+    }
+    #[test]
+    fn test_v2_relative_base() {
+        // Shift the base to 7, then add [rb+0] and [rb+1] into [rb+2]
+        assert_eq!(
+            intcode("109,7,22201,0,1,2,99,3,4,0", false, &[]).unwrap().1,
+            "109,7,22201,0,1,2,99,3,4,7"
+        );
+        // Adjust the base by a relative-mode read, then write through it
+        assert_eq!(
+            intcode("109,4,209,3,21101,2,3,-2,99", false, &[])
+                .unwrap()
+                .1,
+            "5,4,209,3,21101,2,3,-2,99"
+        );
+    }
+    #[test]
+    fn test_memory_past_program() {
+        // Reads past the end see zeroes, writes grow the tape
+        assert_eq!(
+            intcode("1,100,101,7,99", false, &[]).unwrap().1,
+            "1,100,101,7,99,0,0,0"
+        );
+        assert_eq!(
+            intcode(
+                "1101,2,3,1000000000,2,1000000000,1000000000,0,99",
+                false,
+                &[]
+            )
+            .unwrap()
+            .0,
+            25
+        );
+    }
+    #[test]
+    fn test_strict_load() {
+        assert_eq!(
+            intcode("1,0,0,0,99,\n", false, &[]).unwrap().1,
+            "2,0,0,0,99"
+        );
+        let err = IntcodeComputer::new("1,0,0,0;99", &[]).unwrap_err();
+        assert_eq!((err.token_index, err.offset), (3, 6));
+    }
+    #[test]
+    fn test_queued_io() {
+        assert_eq!(intcode_outputs("3,0,4,0,99", &[42]).unwrap(), vec![42]);
+        assert_eq!(
+            intcode_outputs("3,0,3,1,4,1,4,0,99", &[7, 9]).unwrap(),
+            vec![9, 7]
+        );
+        // Bad programs and runs report an error rather than panicking
+        assert!(intcode_outputs("3,0,4,0,99", &[]).is_err());
+        assert!(intcode_outputs("3,x,99", &[]).is_err());
+        assert!(intcode("1,0,0,0", true, &[]).is_err());
+        let mut computer = IntcodeComputer::new("3,0,4,0,99", &[]).unwrap();
+        assert!(computer.execute().is_err());
+        let mut computer = IntcodeComputer::new("3,0,4,0,99", &[]).unwrap();
+        computer.push_input(5);
+        computer.execute().unwrap();
+        assert_eq!(computer.drain_outputs(), vec![5]);
+        assert!(computer.io().outputs().is_empty());
+    }
+    #[test]
+    fn test_resume_on_input() {
+        // Echo inputs back until a zero comes in
+        let mut computer = IntcodeComputer::new("3,10,4,10,1005,10,0,99", &[]).unwrap();
+        assert_eq!(computer.run().unwrap(), RunStatus::NeedsInput);
+        assert_eq!(computer.run().unwrap(), RunStatus::NeedsInput);
+        computer.push_input(3);
+        assert_eq!(computer.run().unwrap(), RunStatus::Output(3));
+        assert_eq!(computer.run().unwrap(), RunStatus::NeedsInput);
+        computer.push_input(0);
+        assert_eq!(computer.run().unwrap(), RunStatus::Output(0));
+        assert_eq!(computer.run().unwrap(), RunStatus::Halted);
+        assert_eq!(computer.run().unwrap(), RunStatus::Halted);
+        assert!(computer.io().outputs().is_empty());
+    }
+    #[test]
+    fn test_feedback_loop() {
+        // 2019 day 7 part 2 sample - five machines wired in a ring
+        let program =
+            "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5";
+        let mut amps = [9, 8, 7, 6, 5]
+            .iter()
+            .map(|phase| IntcodeComputer::new(program, &[*phase]).unwrap())
+            .collect::<Vec<IntcodeComputer>>();
+        let mut signal = 0;
+        let mut last = 0;
+        'feedback: loop {
+            for (i, amp) in amps.iter_mut().enumerate() {
+                amp.push_input(signal);
+                match amp.run().unwrap() {
+                    RunStatus::Output(value) => signal = value,
+                    RunStatus::Halted => break 'feedback,
+                    _ => unreachable!(),
+                }
+                if i == 4 {
+                    last = signal;
+                }
+            }
+        }
+        assert_eq!(last, 139629729);
+    }
+    #[test]
+    fn test_snapshot() {
+        // Add the two inputs into cell 0, then output it
+        let mut computer =
+            IntcodeComputer::new("3,20,3,21,109,0,2201,20,21,0,4,0,99", &[]).unwrap();
+        computer.push_input(2);
+        assert_eq!(computer.run().unwrap(), RunStatus::NeedsInput);
+        let checkpoint = computer.snapshot();
+        assert_eq!(checkpoint.pc(), 2);
+        assert_eq!(checkpoint.io().pending_inputs().len(), 0);
+        for (input, expected) in &[(3, 5), (10, 12)] {
+            computer.restore(&checkpoint);
+            computer.push_input(*input);
+            assert_eq!(computer.run().unwrap(), RunStatus::Output(*expected));
+        }
+        let mut branch = IntcodeComputer::from_snapshot(checkpoint.clone());
+        branch.push_input(-2);
+        assert_eq!(branch.run().unwrap(), RunStatus::Output(0));
+        assert_eq!(branch.run().unwrap(), RunStatus::Halted);
+        assert_ne!(branch.snapshot(), checkpoint);
+    }
+    #[test]
+    fn test_trace() {
+        // A Write that can still be read once the computer owns it
+        #[derive(Clone, Default)]
+        struct Shared(Arc<Mutex<Vec<u8>>>);
+        impl Write for Shared {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        let sink = Shared::default();
+        let mut computer = IntcodeComputer::new("3,0,1002,0,3,7,104,33,99", &[4]).unwrap();
+        computer.trace_to(sink.clone());
+        computer.execute().unwrap();
+        computer.stop_trace().unwrap();
+        let trace = String::from_utf8(sink.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            trace.lines().collect::<Vec<&str>>(),
+            vec![
+                r#"{"step":0,"pc":0,"opcode":3,"variant":"Input","operands":[0],"write":{"address":0,"old":3,"new":4}}"#,
+                r#"{"step":1,"pc":2,"opcode":1002,"variant":"Multiply","operands":[4,3,7],"write":{"address":7,"old":33,"new":12}}"#,
+                r#"{"step":2,"pc":6,"opcode":104,"variant":"Output","operands":[12],"write":null}"#,
+                r#"{"step":3,"pc":8,"opcode":99,"variant":"Terminate","operands":[],"write":null}"#,
+            ]
+        );
+
+        // A fault ends the trace with what went wrong
+        let sink = Shared::default();
+        let mut computer = IntcodeComputer::new("1101,1,1,5,98", &[]).unwrap();
+        computer.trace_to(sink.clone());
+        computer.execute().unwrap_err();
+        let trace = String::from_utf8(sink.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            trace.lines().last().unwrap(),
+            r#"{"step":1,"pc":4,"opcode":98,"variant":null,"operands":[],"write":null,"error":"unknown opcode"}"#
+        );
+        let sink = Shared::default();
+        let mut computer = IntcodeComputer::new("2,5,5,0,99,-9223372036854775808", &[]).unwrap();
+        computer.trace_to(sink.clone());
+        computer.execute().unwrap_err();
+        let trace = String::from_utf8(sink.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            trace.lines().collect::<Vec<&str>>(),
+            vec![
+                r#"{"step":0,"pc":0,"opcode":2,"variant":"Multiply","operands":[],"write":null,"error":"MULTIPLY overflowed on -9223372036854775808 and -9223372036854775808"}"#,
+            ]
+        );
+    }
+    #[test]
+    fn test_profile() {
+        // Counts down from 5, then outputs and halts
+        let program = "1001,10,-1,10,1005,10,0,104,7,99,5";
+        let mut computer = IntcodeComputer::new(program, &[]).unwrap();
+        computer.enable_profiling();
+        computer.execute().unwrap();
+        let profile = computer.take_profile().unwrap();
+        assert_eq!(profile.total, 11);
+        assert_eq!(profile.by_variant[&OpcodeVariant::Add], 5);
+        assert_eq!(profile.by_variant[&OpcodeVariant::Output], 1);
+        assert_eq!(&profile.hot_addresses()[..3], &[(0, 5), (4, 5), (7, 1)]);
+        assert_eq!(
+            profile.jumps[&4],
+            JumpCounts {
+                taken: 4,
+                untaken: 1
+            }
+        );
+        assert_eq!(profile.hot_loops(), vec![((4, 0), 4)]);
+        assert_eq!(
+            profile.to_string(),
+            "total instructions: 11
+by opcode:
+  ADD          5
+  JUMPT        5
+  OUTPUT       1
+hot addresses:
+  0000         5
+  0004         5
+  0007         1
+hot loops:
+  0004 -> 0000 4
+jumps (taken/untaken):
+  0004         4/1
+"
+        );
+        assert!(computer.profile().is_none());
+    }
+    #[test]
+    fn test_watchpoints() {
+        // Counts down from 5, then outputs and halts
+        let program = "1001,10,-1,10,1005,10,0,104,7,99,5";
+        let mut computer = IntcodeComputer::new(program, &[]).unwrap();
+        let reads = Arc::new(Mutex::new(0));
+        let counter = reads.clone();
+        computer.watch(
+            10..11,
+            WatchKind::Read,
+            WatchAction::callback(move |_| *counter.lock().unwrap() += 1),
+        );
+        let id = computer.watch(10..11, WatchKind::Write, WatchAction::Pause);
+        let hit = match computer.run().unwrap() {
+            RunStatus::Watch(hit) => hit,
+            status => panic!("Expected a watchpoint, got {:?}", status),
+        };
+        assert_eq!(
+            hit,
+            WatchHit {
+                pc: 0,
+                step: 0,
+                address: 10,
+                access: Access::Write(MemWrite {
+                    address: 10,
+                    old: 5,
+                    new: 4
+                }),
+                cause: WatchCause::Watchpoint(id),
+            }
+        );
+        assert_eq!(hit.to_string(), "write 0010: 5 -> 4 by 0000 (watchpoint 1)");
+        assert_eq!(computer.pc(), 4);
+        assert!(computer.unwatch(id));
+        computer.execute().unwrap();
+        assert_eq!(*reads.lock().unwrap(), 10);
+
+        // An output that trips a watchpoint still hands its value over first
+        let mut computer = IntcodeComputer::new("4,3,99,42", &[]).unwrap();
+        computer.watch(0..10, WatchKind::ReadWrite, WatchAction::Pause);
+        assert_eq!(computer.run().unwrap(), RunStatus::Output(42));
+        assert!(matches!(
+            computer.run().unwrap(),
+            RunStatus::Watch(WatchHit {
+                address: 3,
+                access: Access::Read(42),
+                ..
+            })
+        ));
+        assert_eq!(computer.run().unwrap(), RunStatus::Halted);
+
+        // A pause held back behind an output belongs to the timeline a restore abandons
+        let mut computer = IntcodeComputer::new("4,5,4,5,99,42", &[]).unwrap();
+        let start = computer.snapshot();
+        computer.watch(5..6, WatchKind::Read, WatchAction::Pause);
+        assert_eq!(computer.step().unwrap(), Some(RunStatus::Output(42)));
+        computer.restore(&start);
+        assert_eq!(computer.step().unwrap(), Some(RunStatus::Output(42)));
+        assert!(matches!(
+            computer.step().unwrap(),
+            Some(RunStatus::Watch(WatchHit { pc: 0, step: 0, .. }))
+        ));
+    }
+    #[test]
+    fn test_self_modification() {
+        let flagged = Arc::new(Mutex::new(Vec::new()));
+        let detect = |computer: &mut IntcodeComputer| {
+            let hits = flagged.clone();
+            computer.detect_self_modification(Some(WatchAction::callback(move |hit| {
+                hits.lock().unwrap().push(hit.address)
+            })));
+        };
+        // Patching a terminator before it runs is fine
+        let mut computer = IntcodeComputer::new("1002,4,3,4,33", &[]).unwrap();
+        detect(&mut computer);
+        computer.execute().unwrap();
+        assert!(flagged.lock().unwrap().is_empty());
+        // Overwriting an instruction that already ran is flagged, even if it's the current one
+        let mut computer = IntcodeComputer::new("1101,5,5,0,1101,1,1,2,99", &[]).unwrap();
+        detect(&mut computer);
+        computer.execute().unwrap();
+        assert_eq!(*flagged.lock().unwrap(), vec![0, 2]);
+
+        let mut computer = IntcodeComputer::new("1101,5,5,0,99", &[]).unwrap();
+        computer.detect_self_modification(Some(WatchAction::Pause));
+        assert!(matches!(
+            computer.run().unwrap(),
+            RunStatus::Watch(WatchHit {
+                cause: WatchCause::SelfModifying,
+                ..
+            })
+        ));
+
+        // Cells only count as executed on the current timeline, so once the second instruction
+        // has been stepped back over or restored away, the first can overwrite it unflagged
+        let program = "1101,1,1,9,1101,0,0,10,99,0,0";
+        flagged.lock().unwrap().clear();
+        let mut computer = IntcodeComputer::new(program, &[]).unwrap();
+        let start = computer.snapshot();
+        computer.enable_history(10);
+        detect(&mut computer);
+        computer.step().unwrap();
+        computer.step().unwrap();
+        assert_eq!(computer.step_back(2), 2);
+        computer.poke(3, 5);
+        computer.step().unwrap();
+        computer.restore(&start);
+        computer.step().unwrap();
+        computer.step().unwrap();
+        computer.restore(&start);
+        computer.poke(3, 5);
+        computer.step().unwrap();
+        assert!(flagged.lock().unwrap().is_empty());
+    }
+    #[test]
+    fn test_step_back() {
+        // Read two inputs, add them into cell 0, then clobber cell 1 and output it
+        let program = "3,20,3,21,1,20,21,0,1101,7,0,1,4,1,99";
+        let mut computer = IntcodeComputer::new(program, &[2, 3]).unwrap();
+        computer.enable_history(100);
+        computer.execute().unwrap();
+        assert_eq!(computer.drain_outputs(), vec![7]);
+        let finished = computer.snapshot();
+        assert_eq!(computer.history().unwrap().len(), 5);
+
+        let entry = computer.rewind_to_write(0).unwrap();
+        assert_eq!((entry.pc, entry.step), (4, 2));
+        assert_eq!(
+            (computer.pc(), computer.steps(), computer.result()),
+            (4, 2, 3)
+        );
+        assert_eq!(computer.rewind_to_write(1), None);
+        assert_eq!(computer.step_back(10), 2);
+        assert_eq!(computer.pc(), 0);
+        assert_eq!(computer.io().pending_inputs(), &[2, 3]);
+        // Cells the writes grew into stay, zeroed
+        assert_eq!(computer.to_string(), format!("{},0,0,0,0,0,0,0", program));
+        computer.execute().unwrap();
+        assert_eq!(computer.drain_outputs(), vec![7]);
+        assert_eq!(computer.snapshot(), finished);
+
+        // A full buffer drops the oldest instructions first
+        let mut computer = IntcodeComputer::new(program, &[2, 3]).unwrap();
+        computer.enable_history(2);
+        computer.execute().unwrap();
+        assert_eq!(computer.step_back(5), 2);
+        assert_eq!(computer.pc(), 8);
+        assert_eq!(computer.peek(1), 20);
+    }
+    #[test]
+    fn test_limits() {
+        // Counts down from 5, then outputs and halts
+        let program = "1001,10,-1,10,1005,10,0,104,7,99,5";
+        let mut computer = IntcodeComputer::new(program, &[]).unwrap();
+        computer.set_limits(Limits::steps(4));
+        assert_eq!(
+            computer.run().unwrap(),
+            RunStatus::LimitReached { pc: 0, steps: 4 }
+        );
+        let err = computer.execute().unwrap_err();
+        assert_eq!(err.pc, 0);
+        assert!(matches!(err.kind, ErrorKind::LimitReached(4)));
+        computer.set_limits(Limits::default());
+        computer.execute().unwrap();
+        assert_eq!(computer.drain_outputs(), vec![7]);
+        assert_eq!(computer.steps(), 11);
+
+        let mut computer = IntcodeComputer::new("1105,1,0", &[]).unwrap();
+        computer.set_limits(Limits::timeout(Duration::from_millis(20)));
+        match computer.run().unwrap() {
+            RunStatus::LimitReached { pc, steps } => {
+                assert_eq!(pc, 0);
+                assert!(steps > 0);
+            }
+            status => panic!("Expected a timeout, got {:?}", status),
+        }
+    }
+    #[test]
+    fn test_overflow() {
+        let run = |program: &str, overflow: Overflow, engine: Engine| {
+            let mut computer = IntcodeComputer::new(program, &[]).unwrap();
+            computer.set_overflow(overflow);
+            computer.set_engine(engine);
+            computer.execute().map(|_| computer.result())
+        };
+        let add = "1101,9223372036854775807,1,0,99";
+        let mul = "1102,-9223372036854775807,2,0,99";
+        let base = "109,9223372036854775807,109,1,99";
+        let relative = "109,-10,204,-9223372036854775808,99";
+        for engine in &[Engine::Interpreted, Engine::Predecoded] {
+            assert_eq!(run(add, Overflow::Wrapping, *engine).unwrap(), Int::MIN);
+            assert_eq!(run(add, Overflow::Saturating, *engine).unwrap(), Int::MAX);
+            assert_eq!(run(mul, Overflow::Wrapping, *engine).unwrap(), 2);
+            assert_eq!(run(mul, Overflow::Saturating, *engine).unwrap(), Int::MIN);
+
+            let err = run(add, Overflow::Checked, *engine).unwrap_err();
+            assert_eq!((err.pc, err.opcode), (0, 1101));
+            assert!(matches!(
+                err.kind,
+                ErrorKind::Overflow(OpcodeVariant::Add, Int::MAX, 1)
+            ));
+            assert_eq!(
+                err.to_string(),
+                "pc 0 (opcode 1101): ADD overflowed on 9223372036854775807 and 1"
+            );
+            let err = run(mul, Overflow::Checked, *engine).unwrap_err();
+            assert!(matches!(
+                err.kind,
+                ErrorKind::Overflow(OpcodeVariant::Multiply, _, 2)
+            ));
+            let err = run(base, Overflow::Checked, *engine).unwrap_err();
+            assert_eq!(err.pc, 2);
+            assert!(matches!(
+                err.kind,
+                ErrorKind::Overflow(OpcodeVariant::AdjustBase, _, 1)
+            ));
+            assert!(run(base, Overflow::Wrapping, *engine).is_ok());
+            // A relative address past the end of the cell range never wraps back into memory
+            for overflow in &[Overflow::Checked, Overflow::Wrapping, Overflow::Saturating] {
+                let err = run(relative, *overflow, *engine).unwrap_err();
+                assert_eq!((err.pc, err.mode), (2, Some(ParameterMode::Relative)));
+                assert!(matches!(err.kind, ErrorKind::MemoryFault(Int::MIN)));
+            }
+        }
+    }
+    #[test]
+    fn test_errors() {
+        let mut computer = IntcodeComputer::new("1101,1,1,0,1,-1,0,0,99", &[]).unwrap();
+        let err = computer.execute().unwrap_err();
+        assert_eq!(
+            (err.pc, err.opcode, err.mode),
+            (4, 1, Some(ParameterMode::Position))
+        );
+        assert!(matches!(err.kind, ErrorKind::MemoryFault(-1)));
+
+        let err = IntcodeComputer::new("1,0,0,0,42", &[])
+            .unwrap()
+            .execute()
+            .unwrap_err();
+        assert_eq!((err.pc, err.opcode), (4, 42));
+        assert!(matches!(err.kind, ErrorKind::BadOpcode));
+
+        let err = IntcodeComputer::new("301,0,0,0,99", &[])
+            .unwrap()
+            .execute()
+            .unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::BadMode(3)));
+
+        let err = IntcodeComputer::new("11101,1,1,0,99", &[])
+            .unwrap()
+            .execute()
+            .unwrap_err();
+        assert_eq!(err.mode, Some(ParameterMode::Immediate));
+        assert!(matches!(err.kind, ErrorKind::ImmediateWrite));
+
+        let err = IntcodeComputer::new("3,0,99", &[])
+            .unwrap()
+            .execute()
+            .unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::InputExhausted));
+        assert_eq!(err.to_string(), "pc 0 (opcode 3): input queue exhausted");
+
+        let err = IntcodeComputer::new("1,0,0,0,99", &[])
+            .unwrap()
+            .locate_target(-5)
+            .unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::TargetNotFound(-5)));
     }
 }
@@ -0,0 +1,163 @@
+use super::{Cell, ErrorKind, Int, IntcodeComputer, IntcodeError, RunStatus};
+use std::fmt;
+
+/// A stretch of output from a program that talks in ASCII
+#[derive(Debug, Clone, PartialEq)]
+pub enum AsciiOutput<T = Int> {
+    /// A run of printable characters and whitespace
+    Text(String),
+    /// A value that isn't one - usually an answer
+    Value(T),
+}
+
+impl<T: Cell> fmt::Display for AsciiOutput<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AsciiOutput::Text(text) => write!(f, "{}", text),
+            AsciiOutput::Value(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// Wraps an IntcodeComputer to send it lines of text and read back what it prints
+#[derive(Debug)]
+pub struct AsciiComputer<T = Int> {
+    computer: IntcodeComputer<T>,
+    halted: bool,
+}
+
+impl<T: Cell> AsciiComputer<T> {
+    pub fn new(computer: IntcodeComputer<T>) -> Self {
+        Self {
+            computer,
+            halted: false,
+        }
+    }
+    pub fn computer(&self) -> &IntcodeComputer<T> {
+        &self.computer
+    }
+    pub fn computer_mut(&mut self) -> &mut IntcodeComputer<T> {
+        &mut self.computer
+    }
+    pub fn into_inner(self) -> IntcodeComputer<T> {
+        self.computer
+    }
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+    /// Queue `line` one character code at a time, followed by a newline, then run.  Fails
+    /// without queueing anything if the line isn't all ASCII.
+    pub fn send(&mut self, line: &str) -> Result<Vec<AsciiOutput<T>>, IntcodeError<T>> {
+        if let Some(c) = line.chars().find(|c| !c.is_ascii()) {
+            return Err(self.computer.fault(ErrorKind::NotAscii(c), None));
+        }
+        for c in line.chars().chain(Some('\n')) {
+            self.computer.push_input(T::from_i64(c as i64));
+        }
+        self.run()
+    }
+    /// Run until the program halts or wants input that isn't queued, collecting what it printed.
+    /// Consecutive characters are merged into one Text.
+    pub fn run(&mut self) -> Result<Vec<AsciiOutput<T>>, IntcodeError<T>> {
+        let mut ret = Vec::new();
+        loop {
+            match self.computer.run()? {
+                RunStatus::Output(value) => match printable(&value) {
+                    Some(c) => match ret.last_mut() {
+                        Some(AsciiOutput::Text(text)) => text.push(c),
+                        _ => ret.push(AsciiOutput::Text(c.to_string())),
+                    },
+                    None => ret.push(AsciiOutput::Value(value)),
+                },
+                RunStatus::Halted => {
+                    self.halted = true;
+                    return Ok(ret);
+                }
+                RunStatus::NeedsInput => return Ok(ret),
+                RunStatus::LimitReached { steps, .. } => {
+                    return Err(self.computer.fault(ErrorKind::LimitReached(steps), None))
+                }
+                RunStatus::Watch(_) => {}
+            }
+        }
+    }
+}
+
+/// The character a value prints as, if it's printable ASCII or whitespace
+fn printable<T: Cell>(value: &T) -> Option<char> {
+    let c = value
+        .to_i64()
+        .filter(|v| (0..128).contains(v))
+        .map(|v| v as u8 as char)?;
+    if c.is_ascii_graphic() || c.is_ascii_whitespace() {
+        Some(c)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::assemble;
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    // Prompts with '>', echoes a line back, then prints a large answer and a newline
+    const ECHO: &str = "
+        output #62
+        loop: input -> [c]
+        equals [c], #10 -> [done]
+        jumpt [done], #finish
+        output [c]
+        jumpt #1, #loop
+        finish: output #1000000
+        output #10
+        terminate
+        c: data 0
+        done: data 0";
+
+    fn transcript(outputs: &[AsciiOutput]) -> String {
+        outputs.iter().map(|o| o.to_string()).collect()
+    }
+    #[test]
+    fn test_conversation() {
+        let program = assemble(ECHO).unwrap();
+        let mut ascii = AsciiComputer::new(IntcodeComputer::new(&program, &[]).unwrap());
+        assert_eq!(
+            ascii.run().unwrap(),
+            vec![AsciiOutput::Text(">".to_string())]
+        );
+        assert!(!ascii.is_halted());
+        let outputs = ascii.send("hi there").unwrap();
+        assert_eq!(
+            outputs,
+            vec![
+                AsciiOutput::Text("hi there".to_string()),
+                AsciiOutput::Value(1_000_000),
+                AsciiOutput::Text("\n".to_string()),
+            ]
+        );
+        assert_eq!(transcript(&outputs), "hi there1000000\n");
+        assert!(ascii.is_halted());
+        assert_eq!(ascii.run().unwrap(), vec![]);
+    }
+    #[test]
+    fn test_not_ascii() {
+        let program = assemble(ECHO).unwrap();
+        let mut ascii = AsciiComputer::new(IntcodeComputer::new(&program, &[]).unwrap());
+        ascii.run().unwrap();
+        let err = ascii.send("café").unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::NotAscii('é')));
+        assert_eq!(err.kind.to_string(), "cannot send non-ASCII character 'é'");
+        assert!(ascii.computer().io().pending_inputs().is_empty());
+    }
+    #[test]
+    fn test_printable() {
+        assert_eq!(printable::<Int>(&65), Some('A'));
+        assert_eq!(printable::<Int>(&10), Some('\n'));
+        assert_eq!(printable::<Int>(&7), None);
+        assert_eq!(printable::<Int>(&127), None);
+        assert_eq!(printable::<Int>(&-65), None);
+        assert_eq!(printable::<Int>(&200), None);
+    }
+}
@@ -0,0 +1,371 @@
+use super::{Int, OpcodeVariant, ParameterMode};
+use std::{collections::HashMap, error::Error, fmt};
+
+/// An assembly failure, with 1-based line and column
+#[derive(Debug, Clone, PartialEq)]
+pub struct AsmError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl AsmError {
+    fn new(line: usize, column: usize, message: &str) -> Self {
+        Self {
+            line,
+            column,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl Error for AsmError {}
+
+/// A literal or a label, resolved in the second pass
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Literal(Int),
+    Label { name: String, negate: bool },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Operand {
+    value: Value,
+    mode: ParameterMode,
+    column: usize,
+}
+
+#[derive(Debug)]
+enum Item {
+    Instruction(OpcodeVariant, Vec<Operand>),
+    Data(Vec<Operand>),
+}
+
+/// Assemble a program into the comma-separated form `IntcodeComputer::new` loads.
+///
+/// One instruction per line, named like `OpcodeVariant` in any case, e.g. `add [9], #3 -> [9]`.
+/// Operands are `#n` immediate, `[n]` position or `[rb+n]` relative, and `n` can be a label.
+/// `name:` defines a label, `DATA 1, 2, label` emits raw cells, and `;` starts a comment.
+pub fn assemble(source: &str) -> Result<String, AsmError> {
+    let mut labels = HashMap::new();
+    let mut items = Vec::new();
+    let mut address = 0;
+
+    // First pass - parse every line and lay out label addresses
+    for (line_idx, raw_line) in source.lines().enumerate() {
+        let line = line_idx + 1;
+        let code = raw_line.split(';').next().unwrap_or("");
+        let mut rest = code;
+        let mut column = 1;
+        if let Some((label, after)) = split_label(rest) {
+            let label_column = column + leading_ws(rest);
+            if let Ok(listed) = label.parse::<usize>() {
+                // An address prefix from a disassembly listing - just check it lines up
+                if listed != address {
+                    return Err(AsmError::new(
+                        line,
+                        label_column,
+                        &format!("listed address {} but assembling at {}", listed, address),
+                    ));
+                }
+            } else if !is_identifier(label) {
+                return Err(AsmError::new(line, label_column, "invalid label name"));
+            } else if labels.insert(label.to_string(), address as Int).is_some() {
+                return Err(AsmError::new(line, label_column, "duplicate label"));
+            }
+            column += rest.len() - after.len();
+            rest = after;
+        }
+        column += leading_ws(rest);
+        let rest = rest.trim();
+        if rest.is_empty() {
+            continue;
+        }
+        let (word, operands_str) = match rest.find(char::is_whitespace) {
+            Some(idx) => (&rest[..idx], &rest[idx..]),
+            None => (rest, ""),
+        };
+        let operands_column = column + word.len();
+        let operands = split_operands(operands_str)
+            .into_iter()
+            .map(|(offset, text)| (operands_column + offset, text))
+            .collect::<Vec<(usize, &str)>>();
+
+        let item = if word.eq_ignore_ascii_case("DATA") {
+            if operands.is_empty() {
+                return Err(AsmError::new(line, column, "DATA needs at least one value"));
+            }
+            let values = operands
+                .iter()
+                .map(|(col, text)| parse_data(line, *col, text))
+                .collect::<Result<Vec<Operand>, AsmError>>()?;
+            address += values.len();
+            Item::Data(values)
+        } else {
+            let variant = OpcodeVariant::from_mnemonic(word)
+                .ok_or_else(|| AsmError::new(line, column, "unknown mnemonic"))?;
+            let expected = variant.instruction_len() - 1;
+            if operands.len() != expected {
+                return Err(AsmError::new(
+                    line,
+                    column,
+                    &format!(
+                        "{} takes {} operand(s), found {}",
+                        variant.mnemonic(),
+                        expected,
+                        operands.len()
+                    ),
+                ));
+            }
+            let params = operands
+                .iter()
+                .map(|(col, text)| parse_operand(line, *col, text))
+                .collect::<Result<Vec<Operand>, AsmError>>()?;
+            if let Some(idx) = variant.write_parameter() {
+                if params[idx].mode == ParameterMode::Immediate {
+                    return Err(AsmError::new(
+                        line,
+                        params[idx].column,
+                        "destination can't be immediate",
+                    ));
+                }
+            }
+            address += variant.instruction_len();
+            Item::Instruction(variant, params)
+        };
+        items.push((line, item));
+    }
+
+    // Second pass - encode, now every label has an address
+    let resolve = |line: usize, operand: &Operand| -> Result<Int, AsmError> {
+        match &operand.value {
+            Value::Literal(n) => Ok(*n),
+            Value::Label { name, negate } => labels
+                .get(name)
+                .map(|addr| if *negate { -addr } else { *addr })
+                .ok_or_else(|| AsmError::new(line, operand.column, "undefined label")),
+        }
+    };
+    let mut cells = Vec::new();
+    for (line, item) in &items {
+        match item {
+            Item::Instruction(variant, params) => {
+                let mut opcode = *variant as Int;
+                let mut place = 100;
+                for p in params {
+                    opcode += place * p.mode as Int;
+                    place *= 10;
+                }
+                cells.push(opcode);
+                for p in params {
+                    cells.push(resolve(*line, p)?);
+                }
+            }
+            Item::Data(values) => {
+                for v in values {
+                    cells.push(resolve(*line, v)?);
+                }
+            }
+        }
+    }
+    Ok(cells
+        .iter()
+        .map(|c| c.to_string())
+        .collect::<Vec<String>>()
+        .join(","))
+}
+
+fn leading_ws(s: &str) -> usize {
+    s.len() - s.trim_start().len()
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+/// Split `label: rest` into the label and rest
+fn split_label(code: &str) -> Option<(&str, &str)> {
+    let idx = code.find(':')?;
+    Some((code[..idx].trim(), &code[idx + 1..]))
+}
+
+/// Split on `,` and `->`, returning each operand with its byte offset.
+/// A leading `->` is allowed for instructions whose only operand is a destination.
+fn split_operands(s: &str) -> Vec<(usize, &str)> {
+    let mut ret = Vec::new();
+    if s.trim().is_empty() {
+        return ret;
+    }
+    let bytes = s.as_bytes();
+    let mut start = 0;
+    let mut i = 0;
+    while i <= bytes.len() {
+        let arrow = i < bytes.len() && bytes[i] == b'-' && bytes.get(i + 1) == Some(&b'>');
+        if i == bytes.len() || bytes[i] == b',' || arrow {
+            let token = &s[start..i];
+            if !(arrow && ret.is_empty() && token.trim().is_empty()) {
+                ret.push((start + leading_ws(token), token.trim()));
+            }
+            start = i + if arrow { 2 } else { 1 };
+            i = start;
+        } else {
+            i += 1;
+        }
+    }
+    ret
+}
+
+fn parse_value(line: usize, column: usize, text: &str, negate: bool) -> Result<Value, AsmError> {
+    if let Ok(n) = text.parse::<Int>() {
+        let n = if negate { n.checked_neg() } else { Some(n) };
+        n.map(Value::Literal)
+            .ok_or_else(|| AsmError::new(line, column, "offset out of range"))
+    } else if is_identifier(text) {
+        Ok(Value::Label {
+            name: text.to_string(),
+            negate,
+        })
+    } else if text.is_empty() {
+        Err(AsmError::new(line, column, "missing operand"))
+    } else {
+        Err(AsmError::new(line, column, "expected a number or label"))
+    }
+}
+
+fn parse_operand(line: usize, column: usize, text: &str) -> Result<Operand, AsmError> {
+    let (value, mode) = if let Some(imm) = text.strip_prefix('#') {
+        (
+            parse_value(line, column + 1, imm.trim(), false)?,
+            ParameterMode::Immediate,
+        )
+    } else if text.starts_with('[') && text.ends_with(']') {
+        let inner = text[1..text.len() - 1].trim();
+        let lower = inner.to_ascii_lowercase();
+        if lower == "rb" {
+            (Value::Literal(0), ParameterMode::Relative)
+        } else if lower.starts_with("rb+") || lower.starts_with("rb-") {
+            let negate = lower.starts_with("rb-");
+            (
+                parse_value(line, column + 1, inner[3..].trim(), negate)?,
+                ParameterMode::Relative,
+            )
+        } else {
+            (
+                parse_value(line, column + 1, inner, false)?,
+                ParameterMode::Position,
+            )
+        }
+    } else if text.is_empty() {
+        return Err(AsmError::new(line, column, "missing operand"));
+    } else {
+        return Err(AsmError::new(
+            line,
+            column,
+            "operand needs a mode: #n, [n] or [rb+n]",
+        ));
+    };
+    Ok(Operand {
+        value,
+        mode,
+        column,
+    })
+}
+
+fn parse_data(line: usize, column: usize, text: &str) -> Result<Operand, AsmError> {
+    Ok(Operand {
+        value: parse_value(line, column, text, false)?,
+        mode: ParameterMode::Immediate,
+        column,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{disassemble, intcode_outputs, parse_program};
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const COUNTDOWN: &str = "
+        ; Output 3, 2, 1 then stop
+        loop:   output [counter]
+                add [counter], #-1 -> [counter]
+                jumpt [counter], #loop
+                terminate
+        counter: data 3
+    ";
+
+    #[test]
+    fn test_assemble() {
+        assert_eq!(
+            assemble(COUNTDOWN).unwrap(),
+            "4,10,1001,10,-1,10,1005,10,0,99,3"
+        );
+        assert_eq!(
+            intcode_outputs(&assemble(COUNTDOWN).unwrap(), &[]).unwrap(),
+            vec![3, 2, 1]
+        );
+        assert_eq!(
+            assemble("ADJUSTBASE #5\nINPUT -> [rb-2]\nOUTPUT [rb]\nTERMINATE").unwrap(),
+            "109,5,203,-2,204,0,99"
+        );
+    }
+    #[test]
+    fn test_roundtrip() {
+        // day 5 sample, reassembled from its own listing
+        let program = "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99";
+        let tape = parse_program::<Int>(program).unwrap();
+        let listing = disassemble(&tape)
+            .iter()
+            .map(|l| l.to_string())
+            .collect::<Vec<String>>()
+            .join("\n");
+        assert_eq!(assemble(&listing).unwrap(), program);
+    }
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            assemble("add #1, #2 -> [0]\nfoo #1").unwrap_err(),
+            AsmError::new(2, 1, "unknown mnemonic")
+        );
+        assert_eq!(
+            assemble("  add #1, #2 -> #0").unwrap_err(),
+            AsmError::new(1, 17, "destination can't be immediate")
+        );
+        assert_eq!(
+            assemble("start: jumpt #1, #nowhere").unwrap_err(),
+            AsmError::new(1, 18, "undefined label")
+        );
+        assert_eq!(
+            assemble("output [1], [2]").unwrap_err().message,
+            "OUTPUT takes 1 operand(s), found 2"
+        );
+        assert_eq!(
+            assemble("a: data 1\na: data 2").unwrap_err(),
+            AsmError::new(2, 1, "duplicate label")
+        );
+        assert_eq!(
+            assemble("output 5").unwrap_err(),
+            AsmError::new(1, 8, "operand needs a mode: #n, [n] or [rb+n]")
+        );
+        assert_eq!(
+            assemble("output [rb--9223372036854775808]").unwrap_err(),
+            AsmError::new(1, 9, "offset out of range")
+        );
+        assert_eq!(
+            assemble("output [rb--9223372036854775807]").unwrap(),
+            "204,9223372036854775807"
+        );
+    }
+}
@@ -0,0 +1,295 @@
+use num_bigint::BigInt;
+use std::{convert::TryFrom, fmt};
+
+/// A value an IntcodeComputer can hold in a memory cell
+///
+/// Implemented for `i64`, `i128` and `BigInt`, which never overflows.  Narrower types would
+/// have to truncate in `from_i64`, so they aren't cells.
+pub trait Cell:
+    Clone + Default + PartialEq + PartialOrd + fmt::Debug + fmt::Display + Send + Sync + 'static
+{
+    /// Lossless - every i64 fits
+    fn from_i64(n: i64) -> Self;
+    /// None if the value doesn't fit
+    fn to_i64(&self) -> Option<i64>;
+    /// None if the value is negative or doesn't fit
+    fn to_usize(&self) -> Option<usize>;
+    /// Parse a decimal integer, describing why not on failure
+    fn parse_cell(s: &str) -> Result<Self, String>;
+    fn checked_add(&self, rhs: &Self) -> Option<Self>;
+    fn checked_mul(&self, rhs: &Self) -> Option<Self>;
+    fn wrapping_add(&self, rhs: &Self) -> Self;
+    fn wrapping_mul(&self, rhs: &Self) -> Self;
+    fn saturating_add(&self, rhs: &Self) -> Self;
+    fn saturating_mul(&self, rhs: &Self) -> Self;
+    fn is_zero(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// What Add, Multiply and AdjustBase do when a result doesn't fit in a cell
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Overflow {
+    /// Fault with `ErrorKind::Overflow`, naming the operands
+    #[default]
+    Checked,
+    /// Two's complement wraparound
+    Wrapping,
+    /// Clamp to the cell type's minimum or maximum
+    Saturating,
+}
+
+impl Overflow {
+    /// None only if the policy is Checked and the sum overflowed
+    pub fn add<T: Cell>(self, lhs: &T, rhs: &T) -> Option<T> {
+        match self {
+            Overflow::Checked => lhs.checked_add(rhs),
+            Overflow::Wrapping => Some(lhs.wrapping_add(rhs)),
+            Overflow::Saturating => Some(lhs.saturating_add(rhs)),
+        }
+    }
+    /// None only if the policy is Checked and the product overflowed
+    pub fn mul<T: Cell>(self, lhs: &T, rhs: &T) -> Option<T> {
+        match self {
+            Overflow::Checked => lhs.checked_mul(rhs),
+            Overflow::Wrapping => Some(lhs.wrapping_mul(rhs)),
+            Overflow::Saturating => Some(lhs.saturating_mul(rhs)),
+        }
+    }
+}
+
+macro_rules! primitive_cell {
+    ($($t:ty),*) => {$(
+        impl Cell for $t {
+            fn from_i64(n: i64) -> Self {
+                <$t>::from(n)
+            }
+            fn to_i64(&self) -> Option<i64> {
+                i64::try_from(*self).ok()
+            }
+            fn to_usize(&self) -> Option<usize> {
+                usize::try_from(*self).ok()
+            }
+            fn parse_cell(s: &str) -> Result<Self, String> {
+                s.parse::<$t>().map_err(|e| e.to_string())
+            }
+            fn checked_add(&self, rhs: &Self) -> Option<Self> {
+                <$t>::checked_add(*self, *rhs)
+            }
+            fn checked_mul(&self, rhs: &Self) -> Option<Self> {
+                <$t>::checked_mul(*self, *rhs)
+            }
+            fn wrapping_add(&self, rhs: &Self) -> Self {
+                <$t>::wrapping_add(*self, *rhs)
+            }
+            fn wrapping_mul(&self, rhs: &Self) -> Self {
+                <$t>::wrapping_mul(*self, *rhs)
+            }
+            fn saturating_add(&self, rhs: &Self) -> Self {
+                <$t>::saturating_add(*self, *rhs)
+            }
+            fn saturating_mul(&self, rhs: &Self) -> Self {
+                <$t>::saturating_mul(*self, *rhs)
+            }
+        }
+    )*};
+}
+
+primitive_cell!(i64, i128);
+
+impl Cell for BigInt {
+    fn from_i64(n: i64) -> Self {
+        BigInt::from(n)
+    }
+    fn to_i64(&self) -> Option<i64> {
+        i64::try_from(self).ok()
+    }
+    fn to_usize(&self) -> Option<usize> {
+        usize::try_from(self).ok()
+    }
+    fn parse_cell(s: &str) -> Result<Self, String> {
+        s.parse::<BigInt>().map_err(|e| e.to_string())
+    }
+    // Arbitrary precision never overflows, so every policy is the same
+    fn checked_add(&self, rhs: &Self) -> Option<Self> {
+        Some(self + rhs)
+    }
+    fn checked_mul(&self, rhs: &Self) -> Option<Self> {
+        Some(self * rhs)
+    }
+    fn wrapping_add(&self, rhs: &Self) -> Self {
+        self + rhs
+    }
+    fn wrapping_mul(&self, rhs: &Self) -> Self {
+        self * rhs
+    }
+    fn saturating_add(&self, rhs: &Self) -> Self {
+        self + rhs
+    }
+    fn saturating_mul(&self, rhs: &Self) -> Self {
+        self * rhs
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{IntcodeComputer, IoQueue};
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// The same programs and expectations, run on each cell type under both engines
+    macro_rules! cell_suite {
+        ($name:ident, $t:ty) => {
+            mod $name {
+                use super::super::super::{
+                    Cell, Engine, ErrorKind, IntcodeComputer, IoQueue, RunStatus,
+                };
+                use crate::get_puzzle_string;
+                use pretty_assertions::assert_eq;
+
+                fn machine(program: &str, inputs: &[i64], engine: Engine) -> IntcodeComputer<$t> {
+                    let inputs = inputs
+                        .iter()
+                        .map(|i| <$t>::from_i64(*i))
+                        .collect::<Vec<$t>>();
+                    let mut ret =
+                        IntcodeComputer::with_io(program, IoQueue::new(&inputs)).unwrap();
+                    ret.set_engine(engine);
+                    ret
+                }
+                /// Final memory and outputs, checked to match across engines
+                fn run(program: &str, inputs: &[i64]) -> (String, Vec<String>) {
+                    let results = [Engine::Interpreted, Engine::Predecoded]
+                        .iter()
+                        .map(|engine| {
+                            let mut computer = machine(program, inputs, *engine);
+                            computer.execute().unwrap();
+                            let outputs = computer.drain_outputs();
+                            (
+                                computer.to_string(),
+                                outputs.iter().map(|o| o.to_string()).collect(),
+                            )
+                        })
+                        .collect::<Vec<(String, Vec<String>)>>();
+                    assert_eq!(results[0], results[1]);
+                    results[0].clone()
+                }
+                fn outputs(program: &str, inputs: &[i64]) -> Vec<String> {
+                    run(program, inputs).1
+                }
+                #[test]
+                fn test_day2() {
+                    assert_eq!(run("1,0,0,0,99", &[]).0, "2,0,0,0,99");
+                    assert_eq!(
+                        run("1,9,10,3,2,3,11,0,99,30,40,50", &[]).0,
+                        "3500,9,10,70,2,3,11,0,99,30,40,50"
+                    );
+                    let day2 = get_puzzle_string(2).unwrap();
+                    let mut computer = machine(&day2, &[], Engine::default());
+                    let target = <$t>::from_i64(19_690_720);
+                    let (noun, verb) = computer.locate_target(target).unwrap();
+                    assert_eq!((noun.to_i64(), verb.to_i64()), (Some(52), Some(96)));
+                }
+                #[test]
+                fn test_day5() {
+                    assert_eq!(run("1002,4,3,4,33", &[]).0, "1002,4,3,4,99");
+                    let jump = "3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9";
+                    assert_eq!(outputs(jump, &[0]), vec!["0"]);
+                    assert_eq!(outputs(jump, &[5]), vec!["1"]);
+                    let day5 = get_puzzle_string(5).unwrap();
+                    assert_eq!(outputs(&day5, &[5]), vec!["9571668"]);
+                }
+                #[test]
+                fn test_day9() {
+                    let quine = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+                    assert_eq!(outputs(quine, &[]).join(","), quine);
+                    assert_eq!(
+                        outputs("1102,34915192,34915192,7,4,7,99,0", &[]),
+                        vec!["1219070632396864"]
+                    );
+                    assert_eq!(
+                        outputs("104,1125899906842624,99", &[]),
+                        vec!["1125899906842624"]
+                    );
+                    assert_eq!(
+                        run("109,4,209,3,21101,2,3,-2,99", &[]).0,
+                        "5,4,209,3,21101,2,3,-2,99"
+                    );
+                }
+                #[test]
+                fn test_resume() {
+                    let mut computer = machine("3,10,4,10,1005,10,0,99", &[], Engine::default());
+                    assert_eq!(computer.run().unwrap(), RunStatus::NeedsInput);
+                    computer.push_input(<$t>::from_i64(3));
+                    assert_eq!(
+                        computer.run().unwrap(),
+                        RunStatus::Output(<$t>::from_i64(3))
+                    );
+                }
+                #[test]
+                fn test_errors() {
+                    let err = machine("1101,1,1,0,1,-1,0,0,99", &[], Engine::default())
+                        .execute()
+                        .unwrap_err();
+                    assert!(matches!(
+                        &err.kind,
+                        ErrorKind::MemoryFault(addr) if addr.to_i64() == Some(-1)
+                    ));
+                    assert_eq!(
+                        err.to_string(),
+                        "pc 4 (opcode 1, Position mode): memory fault at address -1"
+                    );
+                }
+            }
+        };
+    }
+
+    cell_suite!(cells_i64, i64);
+    cell_suite!(cells_i128, i128);
+    cell_suite!(cells_bigint, num_bigint::BigInt);
+
+    #[test]
+    fn test_wider_than_i128() {
+        // Square the input three times
+        let program = "3,20,2,20,20,20,2,20,20,20,2,20,20,20,4,20,99";
+        let inputs = IoQueue::new(&[BigInt::from_i64(10_000_000_000)]);
+        let mut computer = IntcodeComputer::<BigInt>::with_io(program, inputs).unwrap();
+        computer.execute().unwrap();
+        assert_eq!(
+            computer.drain_outputs()[0].to_string(),
+            format!("1{}", "0".repeat(80))
+        );
+        // Too big to even load into a fixed-width machine
+        let program = format!("104,1{},99", "0".repeat(80));
+        assert!(IntcodeComputer::<i128>::with_io(&program, IoQueue::default()).is_err());
+    }
+    #[test]
+    fn test_overflow_policies() {
+        let (max, two) = (i64::MAX, 2);
+        assert_eq!(Overflow::Checked.add(&max, &1), None);
+        assert_eq!(Overflow::Wrapping.add(&max, &1), Some(i64::MIN));
+        assert_eq!(Overflow::Saturating.mul(&max, &two), Some(i64::MAX));
+        assert_eq!(Overflow::Saturating.mul(&i128::MIN, &2), Some(i128::MIN));
+        assert_eq!(Overflow::Checked.mul(&-4, &two), Some(-8));
+        let big = BigInt::from_i64(i64::MAX);
+        assert_eq!(
+            Overflow::Checked.add(&big, &big).unwrap().to_string(),
+            "18446744073709551614"
+        );
+    }
+    #[test]
+    fn test_conversions() {
+        assert_eq!(<i64 as Cell>::to_usize(&-1), None);
+        assert_eq!(<i128 as Cell>::from_i64(i64::MIN), i64::MIN as i128);
+        assert_eq!(<i128 as Cell>::to_i64(&(1 << 70)), None);
+        assert_eq!(<i128 as Cell>::to_usize(&7), Some(7));
+        let big = BigInt::parse_cell("123456789012345678901234567890").unwrap();
+        assert_eq!(big.to_i64(), None);
+        assert_eq!(
+            big.checked_mul(&BigInt::from_i64(10)).unwrap().to_string(),
+            "1234567890123456789012345678900"
+        );
+        assert!(BigInt::parse_cell("12x").is_err());
+        assert!(BigInt::default().is_zero());
+    }
+}
@@ -0,0 +1,280 @@
+use super::{decode_line, Cell, Int, Line, OpcodeVariant, ParameterMode};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    iter,
+};
+
+/// Where control can go when a basic block finishes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Edge {
+    /// Straight on into the block starting here
+    Fallthrough(usize),
+    /// A JumpT/JumpF with an immediate target, taken to the block starting here
+    Jump(usize),
+    /// A jump whose target comes from memory, or points off the tape
+    Unknown,
+}
+
+/// A run of instructions that's only ever entered at the top and left at the bottom
+#[derive(Debug, Clone, PartialEq)]
+pub struct BasicBlock<T = Int> {
+    pub start: usize,
+    /// Ends with a DATA line if it runs into a cell that doesn't decode as loaded
+    pub lines: Vec<Line<T>>,
+    /// Empty if the block halts or faults
+    pub edges: Vec<Edge>,
+}
+
+impl<T> BasicBlock<T> {
+    /// One past the last cell in the block
+    pub fn end(&self) -> usize {
+        self.lines
+            .last()
+            .map(|l| l.address + l.raw.len())
+            .unwrap_or(self.start)
+    }
+}
+
+/// The basic blocks reachable from address 0, in address order
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControlFlowGraph<T = Int> {
+    pub blocks: Vec<BasicBlock<T>>,
+}
+
+impl<T: Cell> ControlFlowGraph<T> {
+    /// The block starting at `start`
+    pub fn block(&self, start: usize) -> Option<&BasicBlock<T>> {
+        self.blocks
+            .binary_search_by_key(&start, |b| b.start)
+            .ok()
+            .map(|i| &self.blocks[i])
+    }
+    /// Render as a Graphviz digraph - jumps are solid, fallthroughs dashed, unknown targets `?`
+    pub fn to_dot(&self) -> String {
+        let mut ret =
+            String::from("digraph intcode {\n    node [shape=box, fontname=monospace];\n");
+        for block in &self.blocks {
+            let label = block
+                .lines
+                .iter()
+                .map(|l| format!("{}\\l", escape(l.to_string().trim_end())))
+                .collect::<String>();
+            ret.push_str(&format!("    b{} [label=\"{}\"];\n", block.start, label));
+        }
+        for block in &self.blocks {
+            for edge in &block.edges {
+                ret.push_str(&match edge {
+                    Edge::Fallthrough(to) => {
+                        format!("    b{} -> b{} [style=dashed];\n", block.start, to)
+                    }
+                    Edge::Jump(to) => format!("    b{} -> b{};\n", block.start, to),
+                    Edge::Unknown => format!(
+                        "    u{0} [label=\"?\", shape=circle];\n    b{0} -> u{0};\n",
+                        block.start
+                    ),
+                });
+            }
+        }
+        ret.push_str("}\n");
+        ret
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Where control can go after the jump in `line`, or None if it isn't a jump.
+/// A jump on an immediate condition only ever goes one way.
+fn jump_edges<T: Cell>(line: &Line<T>, end: usize) -> Option<Vec<Edge>> {
+    let opcode = line.opcode.as_ref()?;
+    let when = match opcode.variant {
+        OpcodeVariant::JumpT => true,
+        OpcodeVariant::JumpF => false,
+        _ => return None,
+    };
+    let (check, target) = (&opcode.parameters[0], &opcode.parameters[1]);
+    let jump = match target.mode {
+        ParameterMode::Immediate => match target.value.to_usize() {
+            Some(to) if to < end => Edge::Jump(to),
+            _ => Edge::Unknown,
+        },
+        _ => Edge::Unknown,
+    };
+    // Nothing to fall through to after the last instruction on the tape
+    let next = line.address + line.raw.len();
+    let fallthrough = (next < end).then_some(Edge::Fallthrough(next));
+    Some(match check.mode {
+        ParameterMode::Immediate if check.value.is_zero() != when => vec![jump],
+        ParameterMode::Immediate => fallthrough.into_iter().collect(),
+        _ => iter::once(jump).chain(fallthrough).collect(),
+    })
+}
+
+/// Lines that end a block without handing on to the next address
+fn stops<T>(line: &Line<T>) -> bool {
+    match &line.opcode {
+        Some(opcode) => opcode.variant == OpcodeVariant::Terminate,
+        None => true,
+    }
+}
+
+/// Follow every path from address 0, splitting blocks wherever a known jump lands.
+/// Code only reachable through unknown jumps or self-modification - returns from subroutines,
+/// say - isn't found.
+pub fn control_flow_graph<T: Cell>(tape: &[T]) -> ControlFlowGraph<T> {
+    let end = tape.len();
+    let fetch = |a: usize| tape.get(a).cloned().unwrap_or_default();
+    let mut lines = BTreeMap::new();
+    let mut leaders = BTreeSet::new();
+    let mut pending = vec![0];
+    if end > 0 {
+        leaders.insert(0);
+    }
+    while let Some(addr) = pending.pop() {
+        if addr >= end || lines.contains_key(&addr) {
+            continue;
+        }
+        let line = decode_line(fetch, addr, end);
+        let next = addr + line.raw.len();
+        if let Some(edges) = jump_edges(&line, end) {
+            for edge in edges {
+                if let Edge::Jump(to) | Edge::Fallthrough(to) = edge {
+                    leaders.insert(to);
+                    pending.push(to);
+                }
+            }
+        } else if !stops(&line) {
+            pending.push(next);
+        }
+        lines.insert(addr, line);
+    }
+
+    let mut blocks = Vec::new();
+    for &start in leaders.iter().filter(|l| lines.contains_key(l)) {
+        let mut block = BasicBlock {
+            start,
+            lines: Vec::new(),
+            edges: Vec::new(),
+        };
+        let mut addr = start;
+        while let Some(line) = lines.get(&addr) {
+            block.lines.push(line.clone());
+            addr += line.raw.len();
+            if let Some(edges) = jump_edges(line, end) {
+                block.edges = edges;
+                break;
+            }
+            if stops(line) {
+                break;
+            }
+            if leaders.contains(&addr) {
+                block.edges.push(Edge::Fallthrough(addr));
+                break;
+            }
+        }
+        blocks.push(block);
+    }
+    ControlFlowGraph { blocks }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn shape(cfg: &ControlFlowGraph) -> Vec<(usize, usize, Vec<Edge>)> {
+        cfg.blocks
+            .iter()
+            .map(|b| (b.start, b.end(), b.edges.clone()))
+            .collect()
+    }
+    #[test]
+    fn test_blocks() {
+        // Count [14] up until it equals [15], then output it.  Address 14 onwards is data.
+        let tape = [
+            1001, 14, 1, 14, 8, 14, 15, 16, 1006, 16, 0, 4, 14, 99, 0, 3, 0,
+        ];
+        let cfg = control_flow_graph(&tape);
+        assert_eq!(
+            shape(&cfg),
+            vec![
+                (0, 11, vec![Edge::Jump(0), Edge::Fallthrough(11)]),
+                (11, 14, vec![]),
+            ]
+        );
+        assert_eq!(
+            cfg.block(11).unwrap().lines[1].to_string().trim_end(),
+            "0013: TERMINATE                      ; 99"
+        );
+        assert!(cfg.block(4).is_none());
+    }
+    #[test]
+    fn test_edges() {
+        // Jump over two DATA cells, then back into the middle of a run, which splits it.
+        // The indirect jump at the end has no known target.
+        let tape = [
+            1105, 1, 5, 42, 42, 1101, 1, 1, 20, 1101, 2, 2, 21, 1005, 20, 9, 2105, 1, 3, 99,
+        ];
+        assert_eq!(
+            shape(&control_flow_graph(&tape)),
+            vec![
+                (0, 3, vec![Edge::Jump(5)]),
+                (5, 9, vec![Edge::Fallthrough(9)]),
+                (9, 16, vec![Edge::Jump(9), Edge::Fallthrough(16)]),
+                (16, 19, vec![Edge::Unknown]),
+            ]
+        );
+        // A JUMPT on an immediate zero never jumps
+        assert_eq!(
+            shape(&control_flow_graph(&[1105, 0, 7, 99])),
+            vec![(0, 3, vec![Edge::Fallthrough(3)]), (3, 4, vec![])]
+        );
+        // A jump that ends the tape only has somewhere to go if it's taken
+        assert_eq!(
+            shape(&control_flow_graph(&[1105, 1, 3, 1005, 3, 0])),
+            vec![(0, 3, vec![Edge::Jump(3)]), (3, 6, vec![Edge::Jump(0)])]
+        );
+        assert_eq!(
+            shape(&control_flow_graph(&[1105, 0, 0])),
+            vec![(0, 3, vec![])]
+        );
+        // Jumping off the end of the tape, and running into a cell that doesn't decode
+        assert_eq!(
+            shape(&control_flow_graph(&[1106, 0, 50, 99])),
+            vec![(0, 3, vec![Edge::Unknown])]
+        );
+        assert_eq!(
+            shape(&control_flow_graph(&[1006, 6, 5, 104, 1, 77, 0])),
+            vec![
+                (0, 3, vec![Edge::Jump(5), Edge::Fallthrough(3)]),
+                (3, 5, vec![Edge::Fallthrough(5)]),
+                (5, 6, vec![]),
+            ]
+        );
+    }
+    #[test]
+    fn test_dot() {
+        let cfg = control_flow_graph::<Int>(&[1005, 5, 0, 2106, 0, 0]);
+        assert_eq!(
+            cfg.to_dot(),
+            [
+                "digraph intcode {",
+                "    node [shape=box, fontname=monospace];",
+                "    b0 [label=\"0000: JUMPT [5], #0                  ; 1005,5,0\\l\"];",
+                "    b3 [label=\"0003: JUMPF #0, [rb+0]               ; 2106,0,0\\l\"];",
+                "    b0 -> b0;",
+                "    b0 -> b3 [style=dashed];",
+                "    u3 [label=\"?\", shape=circle];",
+                "    b3 -> u3;",
+                "}",
+                "",
+            ]
+            .join("\n")
+        );
+        // No dangling node for a fallthrough past the end
+        let dot = control_flow_graph::<Int>(&[1005, 2, 0]).to_dot();
+        assert!(dot.contains("    b0 -> b0;\n"));
+        assert!(!dot.contains("b3"));
+    }
+}
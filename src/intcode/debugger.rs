@@ -0,0 +1,509 @@
+use super::{Int, IntcodeComputer, OpcodeVariant, RunStatus, WatchAction, WatchKind};
+use std::collections::BTreeSet;
+
+// Values per row in a memory dump
+const DUMP_WIDTH: usize = 8;
+// Instructions kept for stepping backwards, unless the computer already records history
+const HISTORY_LEN: usize = 100_000;
+
+const HELP: &str = "\
+step [N]             execute N instructions (default 1)
+back [N]             undo N instructions (default 1)
+continue             run until a breakpoint, halt, or the input queue runs dry
+break ADDR|MNEMONIC  stop before the instruction at ADDR, or before any MNEMONIC
+delete ADDR|MNEMONIC remove a breakpoint
+breaks               list breakpoints
+watch START [END] [r|w|rw]
+                     stop after any read and/or write of START up to but not including END
+unwatch ID           remove a watchpoint
+lastwrite ADDR       rewind to just before the last instruction that wrote ADDR
+where                show pc, relative base, and the current instruction
+mem START [END]      dump memory from START up to but not including END
+poke ADDR VALUE...   overwrite memory starting at ADDR
+input VALUE...       queue input values
+queue                show pending inputs
+save FILE            write the machine state to FILE
+load FILE            resume from a state written by save
+quit                 exit";
+
+/// Line-oriented command interpreter around an IntcodeComputer
+#[derive(Debug)]
+pub struct Debugger {
+    computer: IntcodeComputer,
+    address_breaks: BTreeSet<usize>,
+    opcode_breaks: Vec<OpcodeVariant>,
+    halted: bool,
+}
+
+impl Debugger {
+    pub fn new(mut computer: IntcodeComputer) -> Self {
+        if computer.history().is_none() {
+            computer.enable_history(HISTORY_LEN);
+        }
+        Self {
+            computer,
+            address_breaks: BTreeSet::new(),
+            opcode_breaks: Vec::new(),
+            halted: false,
+        }
+    }
+    pub fn computer(&self) -> &IntcodeComputer {
+        &self.computer
+    }
+    /// Run one command line, returning the text to show, or None to quit
+    pub fn command(&mut self, line: &str) -> Option<String> {
+        let mut words = line.split_whitespace();
+        let cmd = match words.next() {
+            Some(cmd) => cmd,
+            None => return Some(String::new()),
+        };
+        let args = words.collect::<Vec<&str>>();
+        let reply = match cmd {
+            "s" | "step" => match args.first().map(|n| n.parse::<usize>()) {
+                None => self.step(1),
+                Some(Ok(n)) => self.step(n),
+                Some(Err(_)) => Err(format!("Not a step count: {}", args[0])),
+            },
+            "back" => match args.first().map(|n| n.parse::<usize>()) {
+                None => Ok(self.back(1)),
+                Some(Ok(n)) => Ok(self.back(n)),
+                Some(Err(_)) => Err(format!("Not a step count: {}", args[0])),
+            },
+            "lastwrite" => self.last_write(&args),
+            "c" | "continue" => self.cont(),
+            "b" | "break" => self.set_break(&args),
+            "d" | "delete" => self.delete_break(&args),
+            "breaks" => Ok(self.list_breaks()),
+            "watch" => self.set_watch(&args),
+            "unwatch" => self.delete_watch(&args),
+            "w" | "where" => Ok(self.where_am_i()),
+            "m" | "mem" => self.dump(&args),
+            "p" | "poke" => self.poke(&args),
+            "i" | "input" => self.input(&args),
+            "queue" => Ok(self.queue()),
+            "save" => self.save(&args),
+            "load" => self.load(&args),
+            "h" | "help" => Ok(HELP.to_string()),
+            "q" | "quit" => return None,
+            _ => Err(format!("Unknown command {:?} - try help", cmd)),
+        };
+        Some(match reply {
+            Ok(text) | Err(text) => text,
+        })
+    }
+    fn step(&mut self, n: usize) -> Result<String, String> {
+        let mut ret = Vec::new();
+        for _ in 0..n {
+            match self.single() {
+                Ok(Some(status)) => {
+                    ret.push(describe(&status));
+                    if stops(&status) {
+                        break;
+                    }
+                }
+                Ok(None) => {}
+                // Keep whatever the earlier steps printed
+                Err(e) => {
+                    ret.push(e);
+                    return Err(ret.join("\n"));
+                }
+            }
+        }
+        ret.push(self.where_am_i());
+        Ok(ret.join("\n"))
+    }
+    fn cont(&mut self) -> Result<String, String> {
+        let mut ret = Vec::new();
+        let mut first = true;
+        loop {
+            if !first {
+                if self.address_breaks.contains(&self.computer.pc()) {
+                    ret.push(format!("breakpoint at {:04}", self.computer.pc()));
+                    break;
+                }
+                if let Some(op) = &self.computer.current_line().opcode {
+                    if self.opcode_breaks.contains(&op.variant) {
+                        ret.push(format!("breakpoint on {}", op.variant.mnemonic()));
+                        break;
+                    }
+                }
+            }
+            first = false;
+            match self.single() {
+                Ok(Some(status)) => {
+                    ret.push(describe(&status));
+                    if stops(&status) {
+                        break;
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    ret.push(e);
+                    return Err(ret.join("\n"));
+                }
+            }
+        }
+        ret.push(self.where_am_i());
+        Ok(ret.join("\n"))
+    }
+    fn back(&mut self, n: usize) -> String {
+        let undone = self.computer.step_back(n);
+        if undone > 0 {
+            self.halted = false;
+        }
+        format!("back {}\n{}", undone, self.where_am_i())
+    }
+    fn last_write(&mut self, args: &[&str]) -> Result<String, String> {
+        let addr = parse_address(args.first().ok_or("lastwrite needs an address")?)?;
+        match self.computer.rewind_to_write(addr) {
+            Some(entry) => {
+                self.halted = false;
+                let w = entry.write.expect("rewound to a write");
+                Ok(format!(
+                    "step {} wrote {:04}: {} -> {}\n{}",
+                    entry.step,
+                    addr,
+                    w.old,
+                    w.new,
+                    self.where_am_i()
+                ))
+            }
+            None => Err(format!("No recorded write to {:04}", addr)),
+        }
+    }
+    /// Execute one instruction, returning anything worth reporting
+    fn single(&mut self) -> Result<Option<RunStatus>, String> {
+        if self.halted {
+            return Err("Program has halted".to_string());
+        }
+        let status = self.computer.step().map_err(|e| e.to_string())?;
+        if let Some(RunStatus::Halted) = status {
+            self.halted = true;
+        }
+        Ok(status)
+    }
+    fn set_break(&mut self, args: &[&str]) -> Result<String, String> {
+        let target = args.first().ok_or("break needs an address or mnemonic")?;
+        if let Ok(addr) = target.parse::<usize>() {
+            self.address_breaks.insert(addr);
+            Ok(format!("breakpoint at {:04}", addr))
+        } else if let Some(variant) = OpcodeVariant::from_mnemonic(target) {
+            if !self.opcode_breaks.contains(&variant) {
+                self.opcode_breaks.push(variant);
+            }
+            Ok(format!("breakpoint on {}", variant.mnemonic()))
+        } else {
+            Err(format!("Not an address or mnemonic: {}", target))
+        }
+    }
+    fn delete_break(&mut self, args: &[&str]) -> Result<String, String> {
+        let target = args.first().ok_or("delete needs an address or mnemonic")?;
+        let removed = if let Ok(addr) = target.parse::<usize>() {
+            self.address_breaks.remove(&addr)
+        } else if let Some(variant) = OpcodeVariant::from_mnemonic(target) {
+            let before = self.opcode_breaks.len();
+            self.opcode_breaks.retain(|v| *v != variant);
+            before != self.opcode_breaks.len()
+        } else {
+            false
+        };
+        if removed {
+            Ok(format!("deleted {}", target))
+        } else {
+            Err(format!("No breakpoint {}", target))
+        }
+    }
+    fn set_watch(&mut self, args: &[&str]) -> Result<String, String> {
+        let start = parse_address(args.first().ok_or("watch needs a start address")?)?;
+        let mut rest = &args[1..];
+        let end = match rest.first().map(|s| s.parse::<usize>()) {
+            Some(Ok(end)) => {
+                rest = &rest[1..];
+                end
+            }
+            _ => offset(start, 1)?,
+        };
+        let kind = match rest.first().copied() {
+            None | Some("rw") => WatchKind::ReadWrite,
+            Some("r") => WatchKind::Read,
+            Some("w") => WatchKind::Write,
+            Some(other) => return Err(format!("Not r, w or rw: {}", other)),
+        };
+        let id = self.computer.watch(start..end, kind, WatchAction::Pause);
+        Ok(format!("watchpoint {} on {:04}..{:04}", id, start, end))
+    }
+    fn delete_watch(&mut self, args: &[&str]) -> Result<String, String> {
+        let id = args.first().ok_or("unwatch needs a watchpoint id")?;
+        match id.parse::<usize>() {
+            Ok(n) if self.computer.unwatch(n) => Ok(format!("deleted watchpoint {}", n)),
+            _ => Err(format!("No watchpoint {}", id)),
+        }
+    }
+    fn list_breaks(&self) -> String {
+        let mut ret = self
+            .address_breaks
+            .iter()
+            .map(|a| format!("{:04}", a))
+            .collect::<Vec<String>>();
+        ret.extend(self.opcode_breaks.iter().map(|v| v.mnemonic().to_string()));
+        if ret.is_empty() {
+            "no breakpoints".to_string()
+        } else {
+            ret.join("\n")
+        }
+    }
+    fn where_am_i(&self) -> String {
+        format!(
+            "pc={} rb={} steps={}\n{}",
+            self.computer.pc(),
+            self.computer.relative_base(),
+            self.computer.steps(),
+            self.computer.current_line().to_string().trim_end()
+        )
+    }
+    fn dump(&self, args: &[&str]) -> Result<String, String> {
+        let start = parse_address(args.first().ok_or("mem needs a start address")?)?;
+        let end = match args.get(1) {
+            Some(end) => parse_address(end)?,
+            None => offset(start, DUMP_WIDTH)?,
+        };
+        let mut rows = Vec::new();
+        let mut addr = start;
+        while addr < end {
+            let row_end = addr.saturating_add(DUMP_WIDTH).min(end);
+            let cells = (addr..row_end)
+                .map(|a| self.computer.peek(a).to_string())
+                .collect::<Vec<String>>();
+            rows.push(format!("{:04}: {}", addr, cells.join(" ")));
+            addr = row_end;
+        }
+        Ok(rows.join("\n"))
+    }
+    fn poke(&mut self, args: &[&str]) -> Result<String, String> {
+        if args.len() < 2 {
+            return Err("poke needs an address and at least one value".to_string());
+        }
+        let start = parse_address(args[0])?;
+        let values = parse_values(&args[1..])?;
+        let end = offset(start, values.len())?;
+        for (addr, value) in (start..end).zip(values) {
+            self.computer.poke(addr, value);
+        }
+        self.dump(&[args[0], &end.to_string()])
+    }
+    fn input(&mut self, args: &[&str]) -> Result<String, String> {
+        for value in parse_values(args)? {
+            self.computer.push_input(value);
+        }
+        Ok(self.queue())
+    }
+    fn queue(&self) -> String {
+        let pending = self
+            .computer
+            .io()
+            .pending_inputs()
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<String>>();
+        format!("inputs: [{}]", pending.join(", "))
+    }
+    fn save(&self, args: &[&str]) -> Result<String, String> {
+        let path = args.first().ok_or("save needs a file name")?;
+        self.computer
+            .save_state(path)
+            .map_err(|e| format!("Could not save {}: {}", path, e))?;
+        Ok(format!("saved to {}", path))
+    }
+    fn load(&mut self, args: &[&str]) -> Result<String, String> {
+        let path = args.first().ok_or("load needs a file name")?;
+        let loaded = IntcodeComputer::load_state(path)
+            .map_err(|e| format!("Could not load {}: {}", path, e))?;
+        // Keep breakpoints, watchpoints and history recording, but not the old timeline
+        self.computer.restore(&loaded.snapshot());
+        self.halted = false;
+        Ok(format!("loaded {}\n{}", path, self.where_am_i()))
+    }
+}
+
+/// The line to show for a status from `single`
+fn describe(status: &RunStatus) -> String {
+    match status {
+        RunStatus::Output(value) => format!("output: {}", value),
+        RunStatus::NeedsInput => "waiting for input".to_string(),
+        RunStatus::Watch(hit) => format!("watch: {}", hit),
+        RunStatus::Halted => "halted".to_string(),
+        status => format!("{:?}", status),
+    }
+}
+
+/// Whether a status from `single` should end a `step N` or `continue`
+fn stops(status: &RunStatus) -> bool {
+    matches!(
+        status,
+        RunStatus::NeedsInput | RunStatus::Watch(_) | RunStatus::Halted
+    )
+}
+
+/// The address `len` cells past `start`, if there is one
+fn offset(start: usize, len: usize) -> Result<usize, String> {
+    start
+        .checked_add(len)
+        .ok_or_else(|| format!("Address out of range: {}+{}", start, len))
+}
+
+fn parse_address(s: &str) -> Result<usize, String> {
+    s.parse::<usize>()
+        .map_err(|_| format!("Not an address: {}", s))
+}
+
+fn parse_values(args: &[&str]) -> Result<Vec<Int>, String> {
+    args.iter()
+        .map(|s| s.parse::<Int>().map_err(|_| format!("Not a value: {}", s)))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    // Echo inputs back until a zero comes in
+    const ECHO: &str = "3,10,4,10,1005,10,0,99";
+
+    fn debugger() -> Debugger {
+        Debugger::new(IntcodeComputer::new(ECHO, &[]).unwrap())
+    }
+    #[test]
+    fn test_step_and_input() {
+        let mut db = debugger();
+        assert_eq!(
+            db.command("step").unwrap(),
+            "waiting for input\npc=0 rb=0 steps=0\n0000: INPUT -> [10]                  ; 3,10"
+        );
+        assert_eq!(db.command("input 4 0").unwrap(), "inputs: [4, 0]");
+        assert_eq!(
+            db.command("step 2").unwrap(),
+            "output: 4\npc=4 rb=0 steps=2\n0004: JUMPT [10], #0                 ; 1005,10,0"
+        );
+        assert_eq!(db.command("queue").unwrap(), "inputs: [0]");
+    }
+    #[test]
+    fn test_step_fault() {
+        let mut db = Debugger::new(IntcodeComputer::new("104,7,98", &[]).unwrap());
+        assert_eq!(
+            db.command("step 3").unwrap(),
+            "output: 7\npc 2 (opcode 98): unknown opcode"
+        );
+    }
+    #[test]
+    fn test_breakpoints() {
+        let mut db = debugger();
+        db.command("input 1 2 0");
+        assert_eq!(db.command("break output").unwrap(), "breakpoint on OUTPUT");
+        assert_eq!(db.command("b 4").unwrap(), "breakpoint at 0004");
+        assert!(db
+            .command("c")
+            .unwrap()
+            .starts_with("breakpoint on OUTPUT\npc=2"));
+        assert!(db
+            .command("c")
+            .unwrap()
+            .starts_with("output: 1\nbreakpoint at 0004"));
+        db.command("delete 4");
+        db.command("delete output");
+        assert_eq!(db.command("breaks").unwrap(), "no breakpoints");
+        let reply = db.command("continue").unwrap();
+        assert!(reply.starts_with("output: 2\noutput: 0\nhalted\npc=7"));
+        assert_eq!(db.command("s").unwrap(), "Program has halted");
+    }
+    #[test]
+    fn test_memory() {
+        let mut db = debugger();
+        assert_eq!(db.command("mem 0 3").unwrap(), "0000: 3 10 4");
+        assert_eq!(db.command("poke 10 7 8").unwrap(), "0010: 7 8");
+        assert_eq!(
+            db.command("mem 4 14").unwrap(),
+            "0004: 1005 10 0 99 0 0 7 8\n0012: 0 0"
+        );
+        assert_eq!(db.command("poke x 1").unwrap(), "Not an address: x");
+        let max = usize::MAX;
+        assert_eq!(
+            db.command(&format!("poke {} 1 2", max)).unwrap(),
+            format!("Address out of range: {}+2", max)
+        );
+        assert_eq!(
+            db.command(&format!("mem {}", max)).unwrap(),
+            format!("Address out of range: {}+8", max)
+        );
+        assert_eq!(
+            db.command(&format!("watch {}", max)).unwrap(),
+            format!("Address out of range: {}+1", max)
+        );
+        assert_eq!(
+            db.command(&format!("mem {} {}", max - 1, max)).unwrap(),
+            format!("{:04}: 0", max - 1)
+        );
+        assert_eq!(db.command("quit"), None);
+    }
+    #[test]
+    fn test_back() {
+        let mut db = debugger();
+        db.command("input 4 0");
+        db.command("continue");
+        assert_eq!(
+            db.command("lastwrite 10").unwrap(),
+            "step 3 wrote 0010: 4 -> 0\npc=0 rb=0 steps=3\n0000: INPUT -> [10]                  ; 3,10"
+        );
+        assert_eq!(db.command("queue").unwrap(), "inputs: [0]");
+        assert!(db.command("back 2").unwrap().starts_with("back 2\npc=2"));
+        assert_eq!(db.command("mem 10").unwrap(), "0010: 4 0 0 0 0 0 0 0");
+        assert!(db.command("back 5").unwrap().starts_with("back 1\npc=0"));
+        assert_eq!(
+            db.command("lastwrite 10").unwrap(),
+            "No recorded write to 0010"
+        );
+        assert!(db.command("c").unwrap().contains("halted"));
+    }
+    #[test]
+    fn test_watch() {
+        let mut db = debugger();
+        db.command("input 4 0");
+        assert_eq!(
+            db.command("watch 10 11 w").unwrap(),
+            "watchpoint 0 on 0010..0011"
+        );
+        assert!(db
+            .command("c")
+            .unwrap()
+            .starts_with("watch: write 0010: 0 -> 4 by 0000 (watchpoint 0)\npc=2"));
+        assert_eq!(db.command("unwatch 0").unwrap(), "deleted watchpoint 0");
+        assert_eq!(db.command("unwatch 0").unwrap(), "No watchpoint 0");
+        assert_eq!(db.command("watch 10 x").unwrap(), "Not r, w or rw: x");
+        assert_eq!(db.command("quit"), None);
+    }
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!("icdb-state-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut db = debugger();
+        db.command("input 4 5");
+        db.command("step 3");
+        assert_eq!(
+            db.command(&format!("save {}", path)).unwrap(),
+            format!("saved to {}", path)
+        );
+        db.command("c");
+        assert_eq!(
+            db.command(&format!("load {}", path)).unwrap(),
+            format!(
+                "loaded {}\npc=0 rb=0 steps=3\n0000: INPUT -> [10]                  ; 3,10",
+                path
+            )
+        );
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(db.command("queue").unwrap(), "inputs: [5]");
+        assert!(db
+            .command("load /nonexistent/state.txt")
+            .unwrap()
+            .starts_with("Could not load /nonexistent/state.txt: I/O error"));
+    }
+}
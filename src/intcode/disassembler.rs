@@ -0,0 +1,124 @@
+use super::{Cell, Int, Opcode};
+use std::fmt;
+
+// Raw-value comments line up at this column
+const COMMENT_COLUMN: usize = 36;
+
+/// One line of a listing - either a decoded instruction or a single data cell
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line<T = Int> {
+    pub address: usize,
+    /// None if the cells here don't decode as an instruction
+    pub opcode: Option<Opcode<T>>,
+    /// The cells this line covers
+    pub raw: Vec<T>,
+}
+
+impl<T: Cell> fmt::Display for Line<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match &self.opcode {
+            Some(opcode) => {
+                let mut ret = opcode.variant.mnemonic().to_string();
+                let write_idx = opcode.variant.write_parameter();
+                for (i, p) in opcode.parameters.iter().enumerate() {
+                    let sep = if Some(i) == write_idx {
+                        " -> "
+                    } else if i == 0 {
+                        " "
+                    } else {
+                        ", "
+                    };
+                    ret.push_str(&format!("{}{}", sep, p));
+                }
+                ret
+            }
+            None => format!("DATA {}", self.raw[0]),
+        };
+        let raw = self
+            .raw
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<String>>();
+        let code = format!("{:04}: {}", self.address, text);
+        write!(
+            f,
+            "{:width$} ; {}",
+            code,
+            raw.join(","),
+            width = COMMENT_COLUMN
+        )
+    }
+}
+
+/// Decode a single line at `address`, treating it as data if it doesn't decode or runs past `end`
+pub fn decode_line<T: Cell, F: Fn(usize) -> T>(fetch: F, address: usize, end: usize) -> Line<T> {
+    let opcode = Opcode::decode(&fetch, address)
+        .ok()
+        .filter(|o| address + o.variant.instruction_len() <= end);
+    let len = opcode
+        .as_ref()
+        .map(|o| o.variant.instruction_len())
+        .unwrap_or(1);
+    Line {
+        address,
+        opcode,
+        raw: (address..address + len).map(fetch).collect(),
+    }
+}
+
+/// Linear sweep over a tape, decoding an instruction wherever one fits and falling back to data
+pub fn disassemble<T: Cell>(tape: &[T]) -> Vec<Line<T>> {
+    let mut ret = Vec::new();
+    let mut address = 0;
+    while address < tape.len() {
+        let line = decode_line(
+            |a| tape.get(a).cloned().unwrap_or_default(),
+            address,
+            tape.len(),
+        );
+        address += line.raw.len();
+        ret.push(line);
+    }
+    ret
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    fn listing(tape: &[Int]) -> Vec<String> {
+        disassemble(tape)
+            .iter()
+            .map(|l| l.to_string().trim_end().to_string())
+            .collect()
+    }
+    #[test]
+    fn test_listing() {
+        assert_eq!(
+            listing(&[1002, 9, 3, 9, 3, 9, 204, -2, 99, 42]),
+            vec![
+                "0000: MULTIPLY [9], #3 -> [9]        ; 1002,9,3,9",
+                "0004: INPUT -> [9]                   ; 3,9",
+                "0006: OUTPUT [rb-2]                  ; 204,-2",
+                "0008: TERMINATE                      ; 99",
+                "0009: DATA 42                        ; 42",
+            ]
+        );
+    }
+    #[test]
+    fn test_data_fallback() {
+        // 1105 with only one cell left can't fit, 307 has a bad mode
+        assert_eq!(
+            listing(&[307, 1105, 1]),
+            vec![
+                "0000: DATA 307                       ; 307",
+                "0001: DATA 1105                      ; 1105",
+                "0002: DATA 1                         ; 1",
+            ]
+        );
+        assert_eq!(
+            listing(&[1105, 1, 7]),
+            vec!["0000: JUMPT #1, #7                   ; 1105,1,7"]
+        );
+    }
+}
@@ -0,0 +1,333 @@
+use super::{
+    Budget, Cell, ErrorKind, IntcodeComputer, IntcodeError, Memory, OpcodeVariant, Parameter,
+    RunStatus,
+};
+
+// Addresses past this are decoded every time rather than cached
+const CACHE_LIMIT: usize = 1 << 16;
+// Longest instruction, so a write can only land inside one starting this far back
+const MAX_INSTRUCTION_LEN: usize = 4;
+
+/// How `run` and `execute` carry out instructions
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Engine {
+    /// Decode every instruction from memory as it's reached
+    #[default]
+    Interpreted,
+    /// Decode each address once and reuse it until the code there is overwritten.
+    /// Tracing, profiling, history and watchpoints all fall back to the interpreter.
+    Predecoded,
+}
+
+/// A decoded instruction that fits in a register or two - unused parameters are left defaulted
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Decoded<T> {
+    pub variant: OpcodeVariant,
+    pub params: [Parameter<T>; 3],
+}
+
+/// Decode the instruction at `addr` with integer math and no allocation
+pub(super) fn decode<T: Cell, F: Fn(usize) -> T>(
+    fetch: F,
+    addr: usize,
+) -> Result<Decoded<T>, ErrorKind<T>> {
+    let raw = fetch(addr).to_i64().ok_or(ErrorKind::BadOpcode)?;
+    let variant = OpcodeVariant::new(raw).ok_or(ErrorKind::BadOpcode)?;
+    // Everything above the two opcode digits is one mode digit per parameter
+    let mut modes = raw / 100;
+    let mut params = <[Parameter<T>; 3]>::default();
+    for (i, param) in params
+        .iter_mut()
+        .enumerate()
+        .take(variant.instruction_len() - 1)
+    {
+        *param = Parameter::new(fetch(addr + i + 1), modes % 10)?;
+        modes /= 10;
+    }
+    Ok(Decoded { variant, params })
+}
+
+/// Decoded instructions by address
+#[derive(Debug, Clone)]
+pub(super) struct DecodeCache<T> {
+    slots: Vec<Option<Decoded<T>>>,
+}
+
+impl<T> Default for DecodeCache<T> {
+    fn default() -> Self {
+        Self { slots: Vec::new() }
+    }
+}
+
+impl<T: Cell> DecodeCache<T> {
+    pub fn get(&self, addr: usize) -> Option<&Decoded<T>> {
+        self.slots.get(addr).and_then(Option::as_ref)
+    }
+    pub fn insert(&mut self, addr: usize, decoded: Decoded<T>) {
+        if addr < CACHE_LIMIT {
+            if addr >= self.slots.len() {
+                self.slots.resize(addr + 1, None);
+            }
+            self.slots[addr] = Some(decoded);
+        }
+    }
+    /// Drop every cached instruction that covers `addr`
+    pub fn invalidate(&mut self, addr: usize) {
+        let first = addr.saturating_sub(MAX_INSTRUCTION_LEN - 1);
+        for slot in self.slots.iter_mut().take(addr + 1).skip(first) {
+            *slot = None;
+        }
+    }
+    /// Drop every cached instruction covering a cell that differs between `old` and `new`, for
+    /// when memory is swapped out wholesale rather than written a cell at a time
+    pub fn invalidate_changes(&mut self, old: &Memory<T>, new: &Memory<T>) {
+        for addr in 0..self.slots.len() + MAX_INSTRUCTION_LEN - 1 {
+            if old.cell(addr) != new.cell(addr) {
+                self.invalidate(addr);
+            }
+        }
+    }
+}
+
+/// What a predecoded instruction does, worked out before anything is changed so the cached
+/// decode can be borrowed rather than copied
+enum Effect<T> {
+    Store(usize, T),
+    Input(Result<usize, IntcodeError<T>>),
+    Output(T),
+    Jump(usize),
+    Advance,
+    AdjustBase(T),
+    Halt,
+}
+
+impl<T: Cell> IntcodeComputer<T> {
+    /// The uninstrumented fast path behind `run_budgeted`, with the same semantics as `step`
+    pub(super) fn run_predecoded(
+        &mut self,
+        budget: &Budget,
+    ) -> Result<RunStatus<T>, IntcodeError<T>> {
+        let limited = budget.is_limited();
+        loop {
+            if limited && budget.exhausted(self.steps) {
+                return Ok(RunStatus::LimitReached {
+                    pc: self.current_idx,
+                    steps: budget.spent(self.steps),
+                });
+            }
+            let pc = self.current_idx;
+            let (effect, len) = match self.cache.get(pc) {
+                Some(ins) => (self.effect(ins)?, ins.variant.instruction_len()),
+                None => {
+                    let ins = decode(|addr| self.memory.cell(addr), pc)
+                        .map_err(|kind| self.fault(kind, None))?;
+                    let effect = self.effect(&ins)?;
+                    let len = ins.variant.instruction_len();
+                    self.cache.insert(pc, ins);
+                    (effect, len)
+                }
+            };
+            match effect {
+                Effect::Store(dest, value) => {
+                    self.set_value_at(dest, value);
+                    self.current_idx += len;
+                }
+                Effect::Input(dest) => {
+                    let input = self
+                        .io
+                        .read()
+                        .map_err(|e| self.fault(ErrorKind::Io(e), None))?;
+                    match input {
+                        Some(value) => {
+                            self.set_value_at(dest?, value);
+                            self.current_idx += len;
+                        }
+                        None => return Ok(RunStatus::NeedsInput),
+                    }
+                }
+                Effect::Output(value) => {
+                    self.current_idx += len;
+                    self.steps += 1;
+                    return Ok(RunStatus::Output(value));
+                }
+                Effect::Jump(target) => self.current_idx = target,
+                Effect::Advance => self.current_idx += len,
+                Effect::AdjustBase(base) => {
+                    self.relative_base = base;
+                    self.current_idx += len;
+                }
+                Effect::Halt => return Ok(RunStatus::Halted),
+            }
+            self.steps += 1;
+        }
+    }
+    fn effect(&self, ins: &Decoded<T>) -> Result<Effect<T>, IntcodeError<T>> {
+        let p = &ins.params;
+        use OpcodeVariant::*;
+        Ok(match ins.variant {
+            Add | Multiply | LessThan | Equals => {
+                let lhs = self.read_parameter(&p[0])?;
+                let rhs = self.read_parameter(&p[1])?;
+                let dest = self.write_target(&p[2])?;
+                let value = match ins.variant {
+                    Add | Multiply => self.arithmetic(ins.variant, &lhs, &rhs)?,
+                    LessThan => T::from_i64((lhs < rhs) as i64),
+                    _ => T::from_i64((lhs == rhs) as i64),
+                };
+                Effect::Store(dest, value)
+            }
+            // The destination only matters once an input has actually arrived
+            Input => Effect::Input(self.write_target(&p[0])),
+            Output => Effect::Output(self.read_parameter(&p[0])?),
+            JumpT | JumpF => {
+                let check_val = self.read_parameter(&p[0])?;
+                let jmp = self.read_parameter(&p[1])?;
+                if check_val.is_zero() != (ins.variant == JumpT) {
+                    Effect::Jump(self.jump_target(&jmp, &p[1])?)
+                } else {
+                    Effect::Advance
+                }
+            }
+            AdjustBase => {
+                let offset = self.read_parameter(&p[0])?;
+                Effect::AdjustBase(self.arithmetic(AdjustBase, &self.relative_base, &offset)?)
+            }
+            Terminate => Effect::Halt,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{Int, IoQueue};
+    use super::*;
+    use crate::get_puzzle_string;
+    use pretty_assertions::assert_eq;
+
+    /// Run a program to completion under an engine, returning its final state and outputs
+    fn run_with(engine: Engine, program: &str, inputs: &[Int]) -> (String, Vec<Int>, u64) {
+        let mut computer = IntcodeComputer::new(program, inputs).unwrap();
+        computer.set_engine(engine);
+        computer.execute().unwrap();
+        (
+            computer.to_string(),
+            computer.drain_outputs(),
+            computer.steps(),
+        )
+    }
+    fn assert_engines_agree(program: &str, inputs: &[Int]) -> Vec<Int> {
+        let interpreted = run_with(Engine::Interpreted, program, inputs);
+        let predecoded = run_with(Engine::Predecoded, program, inputs);
+        assert_eq!(interpreted, predecoded);
+        predecoded.1
+    }
+    #[test]
+    fn test_default_engine() {
+        // The predecoded engine is opt-in
+        assert_eq!(Engine::default(), Engine::Interpreted);
+        assert_eq!(
+            IntcodeComputer::new("99", &[]).unwrap().engine,
+            Engine::Interpreted
+        );
+    }
+    #[test]
+    fn test_decode() {
+        let fetch = |addr| [1002, 4, 3, 4][addr] as Int;
+        let decoded = decode(fetch, 0).unwrap();
+        assert_eq!(decoded.variant, OpcodeVariant::Multiply);
+        assert_eq!(
+            decoded
+                .params
+                .iter()
+                .map(|p| p.mode as Int)
+                .collect::<Vec<Int>>(),
+            vec![0, 1, 0]
+        );
+        assert!(matches!(
+            decode(|_| -1 as Int, 0),
+            Err(ErrorKind::BadOpcode)
+        ));
+        assert!(matches!(
+            decode(|_| 301 as Int, 0),
+            Err(ErrorKind::BadMode(3))
+        ));
+    }
+    #[test]
+    fn test_engines_agree() {
+        let quine = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+        assert_eq!(
+            assert_engines_agree(quine, &[]),
+            quine
+                .split(',')
+                .map(|s| s.parse::<Int>().unwrap())
+                .collect::<Vec<Int>>()
+        );
+        assert_engines_agree("1,9,10,3,2,3,11,0,99,30,40,50", &[]);
+        assert_engines_agree("1002,4,3,4,33", &[]);
+        assert_engines_agree("109,4,209,3,21101,2,3,-2,99", &[]);
+        // Day 9's other examples, needing 64-bit cells
+        assert_eq!(
+            assert_engines_agree("1102,34915192,34915192,7,4,7,99,0", &[]),
+            vec![1_219_070_632_396_864]
+        );
+        assert_eq!(
+            assert_engines_agree("104,1125899906842624,99", &[]),
+            vec![1_125_899_906_842_624]
+        );
+        // Day 2 with the 1202 patch applied
+        let day2 = get_puzzle_string(2).unwrap();
+        let mut cells = day2.trim().split(',').collect::<Vec<&str>>();
+        cells[1] = "12";
+        cells[2] = "2";
+        assert_engines_agree(&cells.join(","), &[]);
+        let day5 = get_puzzle_string(5).unwrap();
+        assert_eq!(assert_engines_agree(&day5, &[5]), vec![9571668]);
+        assert_engines_agree(&day5, &[1]);
+    }
+    #[test]
+    fn test_self_modification() {
+        // Adds the operand at cell 1 into [30] until it reaches 11, patching that operand to 3 after the first pass.
+        // A stale decode would keep adding 1 and stop at 11.
+        let program = "101,1,30,30,1007,30,11,31,1006,31,18,1101,0,3,1,1105,1,0,4,30,99";
+        assert_eq!(assert_engines_agree(program, &[]), vec![13]);
+    }
+    #[test]
+    fn test_restore_keeps_cache() {
+        let mut computer = IntcodeComputer::new("1101,1,1,9,4,9,99,0,0,0", &[]).unwrap();
+        computer.set_engine(Engine::Predecoded);
+        let start = computer.snapshot();
+        computer.execute().unwrap();
+        assert_eq!(computer.drain_outputs(), vec![2]);
+
+        // Only the instruction whose operands changed is decoded again
+        let mut other = IntcodeComputer::new("1101,2,2,9,4,9,99,0,0,0", &[]).unwrap();
+        computer.restore(&other.snapshot());
+        assert!(computer.cache.get(0).is_none());
+        assert!(computer.cache.get(4).is_some());
+        computer.execute().unwrap();
+        other.execute().unwrap();
+        assert_eq!(computer.drain_outputs(), vec![4]);
+        assert_eq!(computer.to_string(), other.to_string());
+
+        computer.restore(&start);
+        assert!(computer.cache.get(4).is_some());
+        computer.execute().unwrap();
+        assert_eq!(computer.drain_outputs(), vec![2]);
+    }
+    #[test]
+    fn test_errors_agree() {
+        for program in &["1101,1,1,0,1,-1,0,0,99", "1,0,0,0,42", "1105,1,-4"] {
+            let errors = [Engine::Interpreted, Engine::Predecoded]
+                .iter()
+                .map(|engine| {
+                    let mut computer =
+                        IntcodeComputer::<Int>::with_io(program, IoQueue::default()).unwrap();
+                    computer.set_engine(*engine);
+                    let err = computer.execute().unwrap_err();
+                    (err.pc, err.opcode, err.mode, err.to_string())
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(errors[0], errors[1]);
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use super::{Cell, Int, OpcodeVariant, ParameterMode};
+use std::{error::Error, fmt, io};
+
+/// What went wrong inside an IntcodeComputer
+#[derive(Debug)]
+pub enum ErrorKind<T = Int> {
+    /// The cell at the program counter doesn't name a known instruction
+    BadOpcode,
+    /// A mode digit other than 0, 1 or 2
+    BadMode(Int),
+    /// An access or jump to a negative address, or one too big to represent
+    MemoryFault(T),
+    /// The program asked for input and none was queued
+    InputExhausted,
+    /// A destination parameter was given in immediate mode
+    ImmediateWrite,
+    /// An instruction's result didn't fit in a cell under the Checked overflow policy
+    Overflow(OpcodeVariant, T, T),
+    /// A step or time limit ran out after this many instructions
+    LimitReached(u64),
+    /// Every candidate input was tried without hitting the target value
+    TargetNotFound(T),
+    /// None of this many input search candidates matched
+    NoMatch(u64),
+    /// An input search had more candidates than a u64 can count
+    SearchTooLarge,
+    /// Text sent to an ASCII program contained this character
+    NotAscii(char),
+    /// Reading or writing stdio failed
+    Io(io::Error),
+}
+
+impl<T: Cell> fmt::Display for ErrorKind<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ErrorKind::*;
+        match self {
+            BadOpcode => write!(f, "unknown opcode"),
+            BadMode(mode) => write!(f, "unknown parameter mode {}", mode),
+            MemoryFault(addr) => write!(f, "memory fault at address {}", addr),
+            InputExhausted => write!(f, "input queue exhausted"),
+            ImmediateWrite => write!(f, "cannot write to a parameter in immediate mode"),
+            Overflow(variant, lhs, rhs) => write!(
+                f,
+                "{} overflowed on {} and {}",
+                variant.mnemonic(),
+                lhs,
+                rhs
+            ),
+            LimitReached(steps) => write!(f, "limit reached after {} steps", steps),
+            TargetNotFound(target) => write!(f, "no inputs produce {}", target),
+            NoMatch(candidates) => write!(f, "no match among {} candidates", candidates),
+            SearchTooLarge => write!(f, "too many candidates to search"),
+            NotAscii(c) => write!(f, "cannot send non-ASCII character {:?}", c),
+            Io(e) => write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+/// A failed instruction, along with the machine state that caused it
+#[derive(Debug)]
+pub struct IntcodeError<T = Int> {
+    /// Program counter of the failing instruction
+    pub pc: usize,
+    /// Raw value of the cell at `pc`
+    pub opcode: T,
+    /// Mode of the parameter involved, if the failure came from a parameter
+    pub mode: Option<ParameterMode>,
+    pub kind: ErrorKind<T>,
+}
+
+impl<T> IntcodeError<T> {
+    pub fn new(pc: usize, opcode: T, mode: Option<ParameterMode>, kind: ErrorKind<T>) -> Self {
+        Self {
+            pc,
+            opcode,
+            mode,
+            kind,
+        }
+    }
+}
+
+impl<T: Cell> fmt::Display for IntcodeError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "pc {} (opcode {}", self.pc, self.opcode)?;
+        if let Some(mode) = self.mode {
+            write!(f, ", {:?} mode", mode)?;
+        }
+        write!(f, "): {}", self.kind)
+    }
+}
+
+impl<T: Cell> Error for IntcodeError<T> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self.kind {
+            ErrorKind::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
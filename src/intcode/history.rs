@@ -0,0 +1,100 @@
+use super::{Cell, Int, MemWrite};
+use std::collections::VecDeque;
+
+/// What one executed instruction changed, enough to undo it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HistoryEntry<T = Int> {
+    pub step: u64,
+    pub pc: usize,
+    /// Relative base before the instruction ran
+    pub relative_base: T,
+    pub write: Option<MemWrite<T>>,
+    /// Input value the instruction consumed
+    pub input: Option<T>,
+}
+
+/// The most recent instructions executed, oldest dropped first once full
+#[derive(Debug, Clone, PartialEq)]
+pub struct History<T = Int> {
+    entries: VecDeque<HistoryEntry<T>>,
+    capacity: usize,
+}
+
+impl<T: Cell> History<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity.min(1 << 16)),
+            capacity,
+        }
+    }
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    /// Oldest first
+    pub fn entries(&self) -> &VecDeque<HistoryEntry<T>> {
+        &self.entries
+    }
+    /// Most recent entry that wrote to `address`
+    pub fn last_write_to(&self, address: usize) -> Option<&HistoryEntry<T>> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|e| e.write.as_ref().map(|w| w.address) == Some(address))
+    }
+    pub(super) fn push(&mut self, entry: HistoryEntry<T>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+    pub(super) fn pop(&mut self) -> Option<HistoryEntry<T>> {
+        self.entries.pop_back()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    fn entry(step: u64, write_to: Option<usize>) -> HistoryEntry {
+        HistoryEntry {
+            step,
+            pc: 0,
+            relative_base: 0,
+            write: write_to.map(|address| MemWrite {
+                address,
+                old: 0,
+                new: 1,
+            }),
+            input: None,
+        }
+    }
+    #[test]
+    fn test_bounded() {
+        let mut history = History::new(3);
+        for step in 0..5 {
+            history.push(entry(step, Some(step as usize % 2)));
+        }
+        assert_eq!(
+            history
+                .entries()
+                .iter()
+                .map(|e| e.step)
+                .collect::<Vec<u64>>(),
+            vec![2, 3, 4]
+        );
+        assert_eq!(history.last_write_to(1).unwrap().step, 3);
+        assert_eq!(history.last_write_to(7), None);
+        assert_eq!(history.pop().unwrap().step, 4);
+        assert_eq!(history.len(), 2);
+    }
+}
@@ -0,0 +1,136 @@
+use super::{Cell, Int};
+use std::{
+    collections::VecDeque,
+    io::{self, ErrorKind::*, Write},
+};
+
+/// Input and output buffers for an IntcodeComputer
+///
+/// Inputs are consumed front-first as the program asks for them, outputs pile up until drained.
+/// A stdio queue also prompts on stdin once the pushed inputs run dry and echoes outputs to stdout.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IoQueue<T = Int> {
+    inputs: VecDeque<T>,
+    outputs: VecDeque<T>,
+    stdio: bool,
+}
+
+impl<T> Default for IoQueue<T> {
+    fn default() -> Self {
+        Self {
+            inputs: VecDeque::new(),
+            outputs: VecDeque::new(),
+            stdio: false,
+        }
+    }
+}
+
+impl<T: Cell> IoQueue<T> {
+    pub fn new(inputs: &[T]) -> Self {
+        Self {
+            inputs: inputs.iter().cloned().collect(),
+            ..Self::default()
+        }
+    }
+    pub fn stdio() -> Self {
+        Self {
+            stdio: true,
+            ..Self::default()
+        }
+    }
+    /// Whether this queue falls back to stdin and echoes to stdout
+    pub fn is_stdio(&self) -> bool {
+        self.stdio
+    }
+    pub(super) fn set_stdio(&mut self, stdio: bool) {
+        self.stdio = stdio;
+    }
+    pub fn push_input(&mut self, value: T) {
+        self.inputs.push_back(value);
+    }
+    pub fn extend_inputs(&mut self, values: &[T]) {
+        self.inputs.extend(values.iter().cloned());
+    }
+    pub fn pending_inputs(&self) -> &VecDeque<T> {
+        &self.inputs
+    }
+    /// Put a consumed input back at the front of the queue
+    pub fn unread(&mut self, value: T) {
+        self.inputs.push_front(value);
+    }
+    /// Queue an output without echoing it, as if written earlier and not yet drained
+    pub(super) fn push_output(&mut self, value: T) {
+        self.outputs.push_back(value);
+    }
+    pub fn outputs(&self) -> &VecDeque<T> {
+        &self.outputs
+    }
+    pub fn take_output(&mut self) -> Option<T> {
+        self.outputs.pop_front()
+    }
+    pub fn drain_outputs(&mut self) -> Vec<T> {
+        self.outputs.drain(..).collect()
+    }
+    /// Next input value, or None if the queue is empty and this isn't a stdio queue
+    pub fn read(&mut self) -> Result<Option<T>, io::Error> {
+        if let Some(value) = self.inputs.pop_front() {
+            Ok(Some(value))
+        } else if self.stdio {
+            Ok(Some(Self::prompt()?))
+        } else {
+            Ok(None)
+        }
+    }
+    pub fn write(&mut self, value: T) -> Result<(), io::Error> {
+        if self.stdio {
+            let mut stdout = io::stdout();
+            writeln!(stdout, "{}", value)?;
+            stdout.flush()?;
+        }
+        self.outputs.push_back(value);
+        Ok(())
+    }
+    fn prompt() -> Result<T, io::Error> {
+        let mut stdout = io::stdout();
+        write!(stdout, "Enter value> ")?;
+        stdout.flush()?;
+        let mut line = String::new();
+        if io::stdin().read_line(&mut line)? == 0 {
+            return Err(io::Error::new(
+                UnexpectedEof,
+                "stdin closed while waiting for input",
+            ));
+        }
+        T::parse_cell(line.trim()).map_err(|e| {
+            io::Error::new(
+                InvalidData,
+                format!("Could not parse input {:?}: {}", line.trim(), e),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    #[test]
+    fn test_queue_order() {
+        let mut queue = IoQueue::<Int>::new(&[1, 2]);
+        queue.push_input(3);
+        assert_eq!(queue.read().unwrap(), Some(1));
+        assert_eq!(queue.read().unwrap(), Some(2));
+        assert_eq!(queue.read().unwrap(), Some(3));
+        assert_eq!(queue.read().unwrap(), None);
+    }
+    #[test]
+    fn test_drain_outputs() {
+        let mut queue = IoQueue::<Int>::default();
+        queue.write(4).unwrap();
+        queue.write(5).unwrap();
+        assert_eq!(queue.take_output(), Some(4));
+        queue.write(6).unwrap();
+        assert_eq!(queue.drain_outputs(), vec![5, 6]);
+        assert!(queue.outputs().is_empty());
+    }
+}
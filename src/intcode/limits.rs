@@ -0,0 +1,104 @@
+use std::time::{Duration, Instant};
+
+/// Caps on how long a single `run` or `execute` call may go before handing control back
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Limits {
+    pub max_steps: Option<u64>,
+    pub timeout: Option<Duration>,
+}
+
+impl Limits {
+    pub fn steps(max_steps: u64) -> Self {
+        Self {
+            max_steps: Some(max_steps),
+            ..Self::default()
+        }
+    }
+    pub fn timeout(timeout: Duration) -> Self {
+        Self {
+            timeout: Some(timeout),
+            ..Self::default()
+        }
+    }
+}
+
+/// Tracks one call's progress against its Limits
+#[derive(Debug)]
+pub(super) struct Budget {
+    limits: Limits,
+    started: Instant,
+    start_steps: u64,
+}
+
+impl Budget {
+    pub(super) fn new(limits: Limits, start_steps: u64) -> Self {
+        Self {
+            limits,
+            started: Instant::now(),
+            start_steps,
+        }
+    }
+    /// Steps run since this budget was opened
+    pub(super) fn spent(&self, steps: u64) -> u64 {
+        steps - self.start_steps
+    }
+    /// Whether there's anything to check at all
+    pub(super) fn is_limited(&self) -> bool {
+        self.limits.max_steps.is_some() || self.limits.timeout.is_some()
+    }
+    pub(super) fn exhausted(&self, steps: u64) -> bool {
+        if let Some(max_steps) = self.limits.max_steps {
+            if self.spent(steps) >= max_steps {
+                return true;
+            }
+        }
+        if let Some(timeout) = self.limits.timeout {
+            if self.started.elapsed() >= timeout {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// A machine's own Limits handed out a slice at a time, so whatever drives it can check for
+/// other work between slices
+#[derive(Debug)]
+pub(super) struct Slices {
+    budget: Budget,
+    slice: u64,
+}
+
+impl Slices {
+    pub(super) fn new(own: Limits, slice: u64, steps: u64) -> Self {
+        Self {
+            budget: Budget::new(own, steps),
+            slice,
+        }
+    }
+    /// Limits for the next slice, or None once the machine's own limits have run out
+    pub(super) fn next(&self, steps: u64) -> Option<Limits> {
+        if self.budget.exhausted(steps) {
+            return None;
+        }
+        let own = self.budget.limits;
+        let max_steps = match own.max_steps {
+            Some(max_steps) => (max_steps - self.budget.spent(steps)).min(self.slice),
+            None => self.slice,
+        };
+        Some(Limits {
+            max_steps: Some(max_steps),
+            timeout: own
+                .timeout
+                .map(|t| t.saturating_sub(self.budget.started.elapsed())),
+        })
+    }
+    /// Steps run against the machine's own limits so far
+    pub(super) fn spent(&self, steps: u64) -> u64 {
+        self.budget.spent(steps)
+    }
+    /// Start the machine's own limits over, as a fresh `run` call would
+    pub(super) fn reset(&mut self, steps: u64) {
+        self.budget = Budget::new(self.budget.limits, steps);
+    }
+}
@@ -0,0 +1,102 @@
+use super::{Cell, ErrorKind, Int};
+use std::{collections::BTreeMap, fmt};
+
+// Addresses below this live in a flat Vec, anything above goes in the sparse map
+const DENSE_LIMIT: usize = 1 << 20;
+
+/// Intcode memory - the loaded program followed by an unbounded run of zeroes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Memory<T = Int> {
+    dense: Vec<T>,
+    sparse: BTreeMap<usize, T>,
+}
+
+impl<T: Cell> Memory<T> {
+    pub fn new(cells: Vec<T>) -> Self {
+        Self {
+            dense: cells,
+            sparse: BTreeMap::new(),
+        }
+    }
+    /// Read an address, failing on negative addresses
+    pub fn get(&self, addr: &T) -> Result<T, ErrorKind<T>> {
+        Ok(self.cell(Self::address(addr)?))
+    }
+    /// Read an already-validated address.  Untouched cells are zero.
+    pub fn cell(&self, addr: usize) -> T {
+        if addr < self.dense.len() {
+            self.dense[addr].clone()
+        } else if addr < DENSE_LIMIT {
+            T::default()
+        } else {
+            self.sparse.get(&addr).cloned().unwrap_or_default()
+        }
+    }
+    /// Write an already-validated address, growing memory as needed
+    pub fn set_cell(&mut self, addr: usize, value: T) {
+        // A program longer than DENSE_LIMIT keeps its whole tape in the flat prefix
+        if addr < self.dense.len() || addr < DENSE_LIMIT {
+            if addr >= self.dense.len() {
+                self.dense.resize(addr + 1, T::default());
+            }
+            self.dense[addr] = value;
+        } else {
+            self.sparse.insert(addr, value);
+        }
+    }
+    /// Written cells too far out for the flat prefix, in address order
+    pub fn sparse_cells(&self) -> impl Iterator<Item = (usize, &T)> {
+        self.sparse.iter().map(|(addr, value)| (*addr, value))
+    }
+    /// Validate a raw address, which can't be negative or too big for a usize
+    pub fn address(addr: &T) -> Result<usize, ErrorKind<T>> {
+        addr.to_usize()
+            .ok_or_else(|| ErrorKind::MemoryFault(addr.clone()))
+    }
+}
+
+impl<T: Cell> fmt::Display for Memory<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let cells = self
+            .dense
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<String>>();
+        write!(f, "{}", cells.join(","))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    #[test]
+    fn test_grows_on_write() {
+        let mut memory = Memory::<Int>::new(vec![1, 2, 3]);
+        assert_eq!(memory.get(&10).unwrap(), 0);
+        assert_eq!(memory.to_string(), "1,2,3");
+        memory.set_cell(5, 7);
+        assert_eq!(memory.to_string(), "1,2,3,0,0,7");
+    }
+    #[test]
+    fn test_sparse_addresses() {
+        let mut memory = Memory::<Int>::new(vec![1]);
+        memory.set_cell(1_000_000_000_000, 42);
+        assert_eq!(memory.get(&1_000_000_000_000).unwrap(), 42);
+        assert_eq!(memory.get(&999_999_999_999).unwrap(), 0);
+        assert_eq!(memory.to_string(), "1");
+    }
+    #[test]
+    fn test_long_tape() {
+        let mut memory = Memory::<Int>::new(vec![0; DENSE_LIMIT + 10]);
+        memory.set_cell(DENSE_LIMIT + 5, 7);
+        assert_eq!(memory.get(&(DENSE_LIMIT as Int + 5)).unwrap(), 7);
+        assert_eq!(memory.sparse_cells().count(), 0);
+    }
+    #[test]
+    fn test_negative_address() {
+        let memory = Memory::<Int>::new(vec![1]);
+        assert!(matches!(memory.get(&-1), Err(ErrorKind::MemoryFault(-1))));
+        assert!(Memory::<Int>::address(&-1).is_err());
+    }
+}
@@ -0,0 +1,318 @@
+use super::{limits::Slices, Cell, ErrorKind, Int, IntcodeComputer, IntcodeError, RunStatus};
+use std::{convert::TryFrom, error::Error, fmt};
+
+/// Where packets go that aren't for any machine on the network - the NAT on day 23
+pub const MONITOR_ADDRESS: usize = 255;
+// Instructions a machine runs before yielding its turn
+const SLICE: u64 = 100_000;
+
+/// An (address, x, y) triple output by one machine
+#[derive(Debug, Clone, PartialEq)]
+pub struct Packet<T = Int> {
+    pub from: usize,
+    pub to: T,
+    pub x: T,
+    pub y: T,
+}
+
+impl<T: Cell> fmt::Display for Packet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} -> {}: ({}, {})", self.from, self.to, self.x, self.y)
+    }
+}
+
+/// Why a network round stopped early
+#[derive(Debug)]
+pub enum NetworkError<T = Int> {
+    Fault {
+        machine: usize,
+        error: IntcodeError<T>,
+    },
+    /// Neither a machine nor the monitor
+    BadAddress(T),
+}
+
+impl<T: Cell> fmt::Display for NetworkError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NetworkError::Fault { machine, error } => write!(f, "machine {}: {}", machine, error),
+            NetworkError::BadAddress(address) => write!(f, "no machine at address {}", address),
+        }
+    }
+}
+
+impl<T: Cell> Error for NetworkError<T> {}
+
+/// Intcode machines exchanging packets, run one after another on a single thread
+///
+/// Each machine is handed its own address as its first input.  Machines take turns in address
+/// order, each running until it asks for input that hasn't arrived - it's given -1 and its turn
+/// ends.  Packets are delivered as soon as they're sent, so the same programs always produce
+/// the same traffic.  A machine's own Limits apply across turns, starting over whenever it
+/// outputs or is given input, as a fresh `run` call would.
+#[derive(Debug)]
+pub struct Network<T = Int> {
+    machines: Vec<IntcodeComputer<T>>,
+    slices: Vec<Slices>,
+    /// Output values not yet making up a whole packet, per machine
+    partial: Vec<Vec<T>>,
+    monitor_address: usize,
+    monitor: Vec<Packet<T>>,
+    rounds: u64,
+}
+
+impl<T: Cell> Network<T> {
+    pub fn new(mut machines: Vec<IntcodeComputer<T>>) -> Self {
+        for (address, machine) in machines.iter_mut().enumerate() {
+            machine.push_input(T::from_i64(address as i64));
+        }
+        Self {
+            partial: machines.iter().map(|_| Vec::new()).collect(),
+            slices: machines
+                .iter()
+                .map(|m| Slices::new(m.limits, SLICE, m.steps))
+                .collect(),
+            machines,
+            monitor_address: MONITOR_ADDRESS,
+            monitor: Vec::new(),
+            rounds: 0,
+        }
+    }
+    /// Collect packets sent to `address` instead of 255
+    pub fn set_monitor_address(&mut self, address: usize) {
+        self.monitor_address = address;
+    }
+    pub fn machine(&self, address: usize) -> Result<&IntcodeComputer<T>, NetworkError<T>> {
+        self.machines
+            .get(address)
+            .ok_or_else(|| bad_address(address))
+    }
+    /// Full rounds run so far
+    pub fn rounds(&self) -> u64 {
+        self.rounds
+    }
+    /// Packets sent to the monitor address since the last call, oldest first
+    pub fn take_monitor_packets(&mut self) -> Vec<Packet<T>> {
+        self.monitor.drain(..).collect()
+    }
+    /// Queue a packet for `address` as if a machine had sent it, e.g. from the monitor
+    pub fn inject(&mut self, address: usize, x: T, y: T) -> Result<(), NetworkError<T>> {
+        let machine = self
+            .machines
+            .get_mut(address)
+            .ok_or_else(|| bad_address(address))?;
+        machine.push_input(x);
+        machine.push_input(y);
+        Ok(())
+    }
+    /// Give every machine one turn, returning whether the network is idle: nothing was sent and
+    /// every machine that hasn't halted is waiting on an empty queue
+    pub fn round(&mut self) -> Result<bool, NetworkError<T>> {
+        let mut idle = true;
+        for address in 0..self.machines.len() {
+            let (sent, waiting) = self.turn(address)?;
+            idle &= sent == 0 && waiting;
+        }
+        self.rounds += 1;
+        Ok(idle)
+    }
+    /// Run rounds until one finds the network idle
+    pub fn run_until_idle(&mut self) -> Result<(), NetworkError<T>> {
+        while !self.round()? {}
+        Ok(())
+    }
+    /// Run one machine until it blocks, halts or uses up its slice.  Returns the packets it sent
+    /// and whether it ended waiting on input or halted.
+    fn turn(&mut self, address: usize) -> Result<(usize, bool), NetworkError<T>> {
+        let fault = |error| NetworkError::Fault {
+            machine: address,
+            error,
+        };
+        let own_limits = self.machines[address].limits;
+        let mut sent = 0;
+        let status = loop {
+            let machine = &mut self.machines[address];
+            let slices = &mut self.slices[address];
+            machine.limits = match slices.next(machine.steps) {
+                Some(limits) => limits,
+                None => {
+                    let steps = slices.spent(machine.steps);
+                    break Err(fault(machine.fault(ErrorKind::LimitReached(steps), None)));
+                }
+            };
+            match machine.run() {
+                Ok(RunStatus::Output(value)) => {
+                    slices.reset(machine.steps);
+                    self.partial[address].push(value);
+                    if self.partial[address].len() == 3 {
+                        let partial = &mut self.partial[address];
+                        let (y, x, to) = (partial.pop(), partial.pop(), partial.pop());
+                        self.route(Packet {
+                            from: address,
+                            to: to.unwrap(),
+                            x: x.unwrap(),
+                            y: y.unwrap(),
+                        })?;
+                        sent += 1;
+                    }
+                }
+                Ok(RunStatus::NeedsInput) => {
+                    machine.push_input(T::from_i64(-1));
+                    slices.reset(machine.steps);
+                    break Ok(true);
+                }
+                Ok(RunStatus::Halted) => break Ok(true),
+                // Out of its own limits rather than just this slice - fault at the top
+                Ok(RunStatus::LimitReached { .. }) if slices.next(machine.steps).is_none() => {}
+                Ok(RunStatus::LimitReached { .. }) => break Ok(false),
+                Ok(RunStatus::Watch(_)) => {}
+                Err(e) => break Err(fault(e)),
+            }
+        };
+        self.machines[address].limits = own_limits;
+        status.map(|waiting| (sent, waiting))
+    }
+    fn route(&mut self, packet: Packet<T>) -> Result<(), NetworkError<T>> {
+        match packet.to.to_usize() {
+            Some(to) if to == self.monitor_address => self.monitor.push(packet),
+            Some(to) if to < self.machines.len() => self.inject(to, packet.x, packet.y)?,
+            _ => return Err(NetworkError::BadAddress(packet.to)),
+        }
+        Ok(())
+    }
+}
+
+fn bad_address<T: Cell>(address: usize) -> NetworkError<T> {
+    // Nothing past i64::MAX can be a machine, so saturating doesn't change the answer
+    NetworkError::BadAddress(T::from_i64(i64::try_from(address).unwrap_or(i64::MAX)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{assemble, Limits};
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// Sends one packet to machine 1, then spins on input forever
+    const SENDER: &str = "
+        input -> [addr]
+        output #1
+        output #10
+        output #20
+        loop: input -> [x]
+        jumpt #1, #loop
+        addr: data 0
+        x: data 0";
+
+    /// Forwards each packet it gets to `target` with x incremented
+    fn forwarder(target: usize) -> IntcodeComputer {
+        let source = format!(
+            "
+            input -> [addr]
+            loop: input -> [x]
+            equals [x], #-1 -> [t]
+            jumpt [t], #loop
+            input -> [y]
+            add [x], #1 -> [x]
+            output #{}
+            output [x]
+            output [y]
+            jumpt #1, #loop
+            addr: data 0
+            x: data 0
+            y: data 0
+            t: data 0",
+            target
+        );
+        IntcodeComputer::new(&assemble(&source).unwrap(), &[]).unwrap()
+    }
+    fn sender() -> IntcodeComputer {
+        IntcodeComputer::new(&assemble(SENDER).unwrap(), &[]).unwrap()
+    }
+    #[test]
+    fn test_routing() {
+        let mut network = Network::new(vec![sender(), forwarder(MONITOR_ADDRESS)]);
+        // Machine 1 picks up machine 0's packet in the same round, so it's idle by the second
+        assert_eq!(network.round().unwrap(), false);
+        assert_eq!(network.round().unwrap(), true);
+        let packets = network.take_monitor_packets();
+        assert_eq!(
+            packets,
+            vec![Packet {
+                from: 1,
+                to: 255,
+                x: 11,
+                y: 20
+            }]
+        );
+        assert_eq!(packets[0].to_string(), "1 -> 255: (11, 20)");
+        assert!(network.take_monitor_packets().is_empty());
+    }
+    #[test]
+    fn test_monitor_loop() {
+        // Day 23 part 2 style: whenever the network goes idle, send the last monitored packet
+        // to machine 0, until the same y goes out twice in a row
+        let mut network = Network::new(vec![forwarder(1), forwarder(2), forwarder(255)]);
+        network.inject(0, 0, 7).unwrap();
+        let mut delivered = Vec::new();
+        loop {
+            network.run_until_idle().unwrap();
+            let last = network.take_monitor_packets().pop().unwrap();
+            if delivered.last().map(|(_, y)| *y) == Some(last.y) {
+                break;
+            }
+            delivered.push((last.x, last.y));
+            network.inject(0, last.x, last.y).unwrap();
+        }
+        assert_eq!(delivered, vec![(3, 7)]);
+        assert_eq!(network.rounds(), 4);
+    }
+    #[test]
+    fn test_own_limits() {
+        // A limit longer than a slice still only takes a slice per round, and is counted
+        // across rounds
+        let mut spinner = IntcodeComputer::new("1105,1,0", &[]).unwrap();
+        spinner.set_limits(Limits::steps(SLICE * 2 + 1));
+        let mut network = Network::new(vec![spinner, sender()]);
+        assert_eq!(network.round().unwrap(), false);
+        assert_eq!(network.machine(0).unwrap().steps(), SLICE);
+        assert_eq!(
+            network.machine(0).unwrap().limits,
+            Limits::steps(SLICE * 2 + 1)
+        );
+        assert_eq!(network.round().unwrap(), false);
+        match network.round().unwrap_err() {
+            NetworkError::Fault { machine, error } => {
+                assert_eq!(machine, 0);
+                assert!(
+                    matches!(error.kind, ErrorKind::LimitReached(steps) if steps == SLICE * 2 + 1)
+                );
+            }
+            e => panic!("unexpected {}", e),
+        }
+        assert_eq!(network.rounds(), 2);
+    }
+    #[test]
+    fn test_errors() {
+        let mut network = Network::new(vec![forwarder(9)]);
+        network.inject(0, 1, 2).unwrap();
+        let err = network.run_until_idle().unwrap_err();
+        assert_eq!(err.to_string(), "no machine at address 9");
+        let err = network.inject(1, 1, 2).unwrap_err();
+        assert_eq!(err.to_string(), "no machine at address 1");
+        assert!(network.machine(0).is_ok());
+        assert!(matches!(
+            network.machine(1),
+            Err(NetworkError::BadAddress(1))
+        ));
+
+        let crash = IntcodeComputer::new("3,0,4,-1,99", &[]).unwrap();
+        let mut network = Network::new(vec![sender(), crash]);
+        match network.round().unwrap_err() {
+            NetworkError::Fault { machine, error } => {
+                assert_eq!((machine, error.pc), (1, 2));
+            }
+            e => panic!("unexpected {}", e),
+        }
+    }
+}
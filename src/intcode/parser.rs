@@ -0,0 +1,111 @@
+use super::Cell;
+use std::{error::Error, fmt};
+
+/// Why a program string was rejected
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrorKind {
+    /// Nothing but whitespace and commas
+    Empty,
+    /// Two commas with nothing between them
+    MissingValue,
+    /// A token that isn't an integer, or doesn't fit the cell type - also what a missing comma looks like
+    InvalidInt(String),
+}
+
+/// Location and cause of the first bad token in a program string
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    /// Zero-based position of the token in the comma-separated list, i.e. the address it would load at
+    pub token_index: usize,
+    /// Byte offset of the token within the input
+    pub offset: usize,
+    pub token: String,
+    pub kind: ParseErrorKind,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ParseErrorKind::*;
+        match &self.kind {
+            Empty => write!(f, "empty program"),
+            MissingValue => write!(
+                f,
+                "missing value at token {} (byte {})",
+                self.token_index, self.offset
+            ),
+            InvalidInt(e) => write!(
+                f,
+                "bad token {:?} at token {} (byte {}): {}",
+                self.token, self.token_index, self.offset, e
+            ),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// Strictly parse a comma-separated Intcode program.
+///
+/// Whitespace around tokens and trailing commas are fine, anything else that isn't an integer is an error.
+pub fn parse_program<T: Cell>(input: &str) -> Result<Vec<T>, ParseError> {
+    let mut ret = Vec::new();
+    // Trailing commas and whitespace don't count as missing values
+    let body = input.trim_end_matches(|c: char| c == ',' || c.is_whitespace());
+    if body.trim().is_empty() {
+        return Err(ParseError {
+            token_index: 0,
+            offset: 0,
+            token: String::new(),
+            kind: ParseErrorKind::Empty,
+        });
+    }
+    let mut offset = 0;
+    for (token_index, raw) in body.split(',').enumerate() {
+        let token = raw.trim();
+        let token_offset = offset + (raw.len() - raw.trim_start().len());
+        let error = |kind| ParseError {
+            token_index,
+            offset: token_offset,
+            token: token.to_string(),
+            kind,
+        };
+        if token.is_empty() {
+            return Err(error(ParseErrorKind::MissingValue));
+        }
+        ret.push(T::parse_cell(token).map_err(|e| error(ParseErrorKind::InvalidInt(e)))?);
+        offset += raw.len() + 1;
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::Int;
+    use super::*;
+    use pretty_assertions::assert_eq;
+    #[test]
+    fn test_tolerated() {
+        assert_eq!(parse_program::<Int>("1,2,3").unwrap(), vec![1, 2, 3]);
+        assert_eq!(parse_program::<Int>(" 1, -2 ,3\n").unwrap(), vec![1, -2, 3]);
+        assert_eq!(parse_program::<Int>("1,\n2,\n3,\n").unwrap(), vec![1, 2, 3]);
+        assert_eq!(parse_program::<Int>("99,,").unwrap(), vec![99]);
+    }
+    #[test]
+    fn test_positions() {
+        let err = parse_program::<Int>("1,2,x3,4").unwrap_err();
+        assert_eq!((err.token_index, err.offset), (2, 4));
+        assert_eq!(err.token, "x3");
+        let err = parse_program::<Int>("1,0,0 0,99").unwrap_err();
+        assert_eq!(
+            (err.token_index, err.offset, err.token.as_str()),
+            (2, 4, "0 0")
+        );
+        let err = parse_program::<Int>("1,  ,3").unwrap_err();
+        assert_eq!((err.token_index, err.offset), (1, 4));
+        assert_eq!(err.kind, ParseErrorKind::MissingValue);
+        assert_eq!(
+            parse_program::<Int>(" ,\n").unwrap_err().kind,
+            ParseErrorKind::Empty
+        );
+    }
+}
@@ -0,0 +1,421 @@
+use super::{limits::Slices, Cell, ErrorKind, Int, IntcodeComputer, IntcodeError, RunStatus};
+use std::{
+    error::Error,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    },
+    thread,
+    time::Duration,
+};
+
+// How often a machine blocked on input checks whether the pipeline has stopped or deadlocked
+const POLL: Duration = Duration::from_millis(5);
+// Most instructions a machine runs between checks for a stop
+const SLICE: u64 = 100_000;
+
+/// How one machine in a pipeline finished
+#[derive(Debug)]
+pub enum Outcome<T = Int> {
+    Halted,
+    /// Waiting on input that can never arrive - every machine feeding it is done, or all are waiting
+    Starved,
+    /// Stopped early because another machine faulted
+    Stopped,
+    Faulted(IntcodeError<T>),
+}
+
+/// Everything left once a pipeline has finished
+#[derive(Debug)]
+pub struct PipelineReport<T = Int> {
+    /// Each machine in the order it was added, with anything it output that had no link to go to
+    pub machines: Vec<(IntcodeComputer<T>, Outcome<T>)>,
+    /// The last value sent down each link in the order they were connected, whether or not it
+    /// was ever read
+    pub links: Vec<Option<T>>,
+}
+
+impl<T> PipelineReport<T> {
+    /// The first machine that faulted, if any did
+    pub fn fault(&self) -> Option<(usize, &IntcodeError<T>)> {
+        self.machines
+            .iter()
+            .enumerate()
+            .find_map(|(i, (_, o))| match o {
+                Outcome::Faulted(e) => Some((i, e)),
+                _ => None,
+            })
+    }
+}
+
+/// A link named a machine index that was never added
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoSuchMachine(pub usize);
+
+impl fmt::Display for NoSuchMachine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "no machine {} in the pipeline", self.0)
+    }
+}
+
+impl Error for NoSuchMachine {}
+
+/// Intcode machines on their own threads, with outputs wired to inputs by channels
+///
+/// A machine's outputs go to every link leaving it, and a machine with several links coming in
+/// reads whichever value arrives first.  Inputs already queued on a machine are read before
+/// anything from its links, so phase settings and seed values can be pushed before it's added.
+#[derive(Debug)]
+pub struct Pipeline<T = Int> {
+    machines: Vec<IntcodeComputer<T>>,
+    links: Vec<(usize, usize)>,
+}
+
+impl<T> Default for Pipeline<T> {
+    fn default() -> Self {
+        Self {
+            machines: Vec::new(),
+            links: Vec::new(),
+        }
+    }
+}
+
+/// One machine's progress, as seen by the others
+#[derive(Debug)]
+struct Slot {
+    live: AtomicBool,
+    waiting: AtomicBool,
+    /// Values sent to this machine and values it has read - equal when its channel is empty
+    sent: AtomicUsize,
+    received: AtomicUsize,
+}
+
+/// State shared by every machine in a running pipeline
+#[derive(Debug)]
+struct Shared {
+    stop: AtomicBool,
+    /// Every send ever, so a deadlock check can tell if anything moved while it looked
+    sends: AtomicUsize,
+    slots: Vec<Slot>,
+}
+
+impl Shared {
+    fn new(machines: usize) -> Self {
+        Self {
+            stop: AtomicBool::new(false),
+            sends: AtomicUsize::new(0),
+            slots: (0..machines)
+                .map(|_| Slot {
+                    live: AtomicBool::new(true),
+                    waiting: AtomicBool::new(false),
+                    sent: AtomicUsize::new(0),
+                    received: AtomicUsize::new(0),
+                })
+                .collect(),
+        }
+    }
+    /// Every machine still running is blocked and nothing is on its way to any of them
+    fn deadlocked(&self) -> bool {
+        let before = self.sends.load(Ordering::SeqCst);
+        let idle = self.slots.iter().all(|s| {
+            !s.live.load(Ordering::SeqCst)
+                || (s.waiting.load(Ordering::SeqCst)
+                    && s.sent.load(Ordering::SeqCst) == s.received.load(Ordering::SeqCst))
+        });
+        idle && self.sends.load(Ordering::SeqCst) == before
+    }
+}
+
+impl<T: Cell> Pipeline<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Add a machine, returning its index
+    pub fn add(&mut self, computer: IntcodeComputer<T>) -> usize {
+        self.machines.push(computer);
+        self.machines.len() - 1
+    }
+    /// Send the outputs of machine `from` to the inputs of machine `to`, returning the link's index
+    pub fn connect(&mut self, from: usize, to: usize) -> Result<usize, NoSuchMachine> {
+        if let Some(bad) = [from, to].iter().find(|i| **i >= self.machines.len()) {
+            return Err(NoSuchMachine(*bad));
+        }
+        self.links.push((from, to));
+        Ok(self.links.len() - 1)
+    }
+    /// Run every machine until they've all halted, starved or stopped
+    pub fn run(self) -> PipelineReport<T> {
+        let shared = Shared::new(self.machines.len());
+        let (senders, mut receivers): (Vec<Sender<T>>, Vec<Option<Receiver<T>>>) = self
+            .machines
+            .iter()
+            .map(|_| {
+                let (tx, rx) = mpsc::channel();
+                (tx, Some(rx))
+            })
+            .unzip();
+        let mut outgoing = self.machines.iter().map(|_| Vec::new()).collect::<Vec<_>>();
+        for (link, (from, to)) in self.links.iter().enumerate() {
+            outgoing[*from].push((link, *to, senders[*to].clone()));
+        }
+        // Only the links hold senders, so a machine's input closes once everything feeding it is done
+        drop(senders);
+
+        let mut links = self.links.iter().map(|_| None).collect::<Vec<Option<T>>>();
+        let machines = thread::scope(|scope| {
+            let handles = self
+                .machines
+                .into_iter()
+                .zip(outgoing)
+                .enumerate()
+                .map(|(i, (computer, outgoing))| {
+                    let input = receivers[i].take().unwrap();
+                    let shared = &shared;
+                    scope.spawn(move || drive(i, computer, input, outgoing, shared))
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|h| h.join().expect("pipeline thread panicked"))
+                .collect::<Vec<_>>()
+        });
+        let machines = machines
+            .into_iter()
+            .map(|(computer, outcome, sent)| {
+                for (link, value) in sent {
+                    links[link] = Some(value);
+                }
+                (computer, outcome)
+            })
+            .collect();
+        PipelineReport { machines, links }
+    }
+}
+
+/// Run one machine on its own thread, returning it with how it finished and the last value
+/// it sent down each of its links
+fn drive<T: Cell>(
+    index: usize,
+    mut computer: IntcodeComputer<T>,
+    input: Receiver<T>,
+    outgoing: Vec<(usize, usize, Sender<T>)>,
+    shared: &Shared,
+) -> (IntcodeComputer<T>, Outcome<T>, Vec<(usize, T)>) {
+    // Every machine runs in slices so it notices a stop, but still faults on its own limits
+    let own_limits = computer.limits;
+    let mut slices = Slices::new(own_limits, SLICE, computer.steps);
+    let mut sent = Vec::new();
+    let outcome = loop {
+        if shared.stop.load(Ordering::SeqCst) {
+            break Outcome::Stopped;
+        }
+        computer.limits = match slices.next(computer.steps) {
+            Some(limits) => limits,
+            None => {
+                let steps = slices.spent(computer.steps);
+                break Outcome::Faulted(computer.fault(ErrorKind::LimitReached(steps), None));
+            }
+        };
+        match computer.run() {
+            Ok(RunStatus::Output(value)) => {
+                slices.reset(computer.steps);
+                if outgoing.is_empty() {
+                    if let Err(e) = computer.io.write(value) {
+                        break Outcome::Faulted(computer.fault(ErrorKind::Io(e), None));
+                    }
+                    continue;
+                }
+                for (link, to, tx) in &outgoing {
+                    shared.slots[*to].sent.fetch_add(1, Ordering::SeqCst);
+                    shared.sends.fetch_add(1, Ordering::SeqCst);
+                    // A finished machine drops its receiver - the value still counts as sent
+                    let _ = tx.send(value.clone());
+                    match sent.iter_mut().find(|(l, _)| l == link) {
+                        Some((_, last)) => *last = value.clone(),
+                        None => sent.push((*link, value.clone())),
+                    }
+                }
+            }
+            Ok(RunStatus::NeedsInput) => match wait(&input, &shared.slots[index], shared) {
+                Ok(value) => {
+                    computer.push_input(value);
+                    slices.reset(computer.steps);
+                }
+                Err(outcome) => break outcome,
+            },
+            Ok(RunStatus::Halted) => break Outcome::Halted,
+            // Checked against the machine's own limits at the top of the loop
+            Ok(RunStatus::LimitReached { .. }) | Ok(RunStatus::Watch(_)) => {}
+            Err(e) => break Outcome::Faulted(e),
+        }
+    };
+    if let Outcome::Faulted(_) = outcome {
+        shared.stop.store(true, Ordering::SeqCst);
+    }
+    computer.limits = own_limits;
+    // Close this machine's links before counting it out, so nothing waits on it
+    drop(outgoing);
+    shared.slots[index].live.store(false, Ordering::SeqCst);
+    (computer, outcome, sent)
+}
+
+/// Block until a value arrives, or return how the machine should finish if none ever will
+fn wait<T>(input: &Receiver<T>, slot: &Slot, shared: &Shared) -> Result<T, Outcome<T>> {
+    slot.waiting.store(true, Ordering::SeqCst);
+    let ret = loop {
+        match input.recv_timeout(POLL) {
+            Ok(value) => {
+                // Stop waiting before the value is counted as read, so nobody sees a moment
+                // where everything looks stuck
+                slot.waiting.store(false, Ordering::SeqCst);
+                slot.received.fetch_add(1, Ordering::SeqCst);
+                return Ok(value);
+            }
+            Err(RecvTimeoutError::Disconnected) => break Err(Outcome::Starved),
+            Err(RecvTimeoutError::Timeout) => {
+                if shared.stop.load(Ordering::SeqCst) {
+                    break Err(Outcome::Stopped);
+                }
+                if shared.deadlocked() {
+                    break Err(Outcome::Starved);
+                }
+            }
+        }
+    };
+    slot.waiting.store(false, Ordering::SeqCst);
+    ret
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::Limits;
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    // 2019 day 7 part 2 sample amplifier
+    const AMP: &str =
+        "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5";
+    // Doubles every input until it reads a zero
+    const DOUBLER: &str = "3,15,1006,15,14,1002,15,2,15,4,15,1105,1,0,99,0";
+
+    fn outcomes<T>(report: &PipelineReport<T>) -> Vec<&'static str> {
+        report
+            .machines
+            .iter()
+            .map(|(_, o)| match o {
+                Outcome::Halted => "halted",
+                Outcome::Starved => "starved",
+                Outcome::Stopped => "stopped",
+                Outcome::Faulted(_) => "faulted",
+            })
+            .collect()
+    }
+    #[test]
+    fn test_feedback_ring() {
+        let mut pipeline = Pipeline::new();
+        // The first amplifier also gets the starting signal
+        for inputs in &[vec![9, 0], vec![8], vec![7], vec![6], vec![5]] {
+            pipeline.add(IntcodeComputer::new(AMP, inputs).unwrap());
+        }
+        for i in 0..5 {
+            pipeline.connect(i, (i + 1) % 5).unwrap();
+        }
+        let report = pipeline.run();
+        assert_eq!(outcomes(&report), vec!["halted"; 5]);
+        assert_eq!(report.links[4], Some(139629729));
+        assert!(report.fault().is_none());
+    }
+    #[test]
+    fn test_fan_out_and_in() {
+        // One source feeds two doublers, which both feed a collector with no links out
+        let mut pipeline = Pipeline::new();
+        let source = pipeline.add(IntcodeComputer::new("104,3,104,5,99", &[]).unwrap());
+        let a = pipeline.add(IntcodeComputer::new(DOUBLER, &[]).unwrap());
+        let b = pipeline.add(IntcodeComputer::new(DOUBLER, &[]).unwrap());
+        let sink = pipeline.add(IntcodeComputer::new("3,7,4,7,1105,1,0,0", &[]).unwrap());
+        pipeline.connect(source, a).unwrap();
+        pipeline.connect(source, b).unwrap();
+        pipeline.connect(a, sink).unwrap();
+        pipeline.connect(b, sink).unwrap();
+        let mut report = pipeline.run();
+        // The doublers and the sink wait for more once the source halts
+        assert_eq!(
+            outcomes(&report),
+            vec!["halted", "starved", "starved", "starved"]
+        );
+        assert_eq!(report.links, vec![Some(5), Some(5), Some(10), Some(10)]);
+        let mut collected = report.machines[sink].0.drain_outputs();
+        collected.sort_unstable();
+        assert_eq!(collected, vec![6, 6, 10, 10]);
+    }
+    #[test]
+    fn test_deadlock() {
+        // Two machines that each wait for the other to go first
+        let mut pipeline = Pipeline::new();
+        let a = pipeline.add(IntcodeComputer::new("3,0,4,0,99", &[]).unwrap());
+        let b = pipeline.add(IntcodeComputer::new("3,0,4,0,99", &[]).unwrap());
+        pipeline.connect(a, b).unwrap();
+        pipeline.connect(b, a).unwrap();
+        let report = pipeline.run();
+        assert_eq!(outcomes(&report), vec!["starved", "starved"]);
+        assert_eq!(report.links, vec![None, None]);
+    }
+    #[test]
+    fn test_fault_stops_everything() {
+        // A spins forever, B waits on A, C faults straight away
+        let mut pipeline = Pipeline::new();
+        let a = pipeline.add(IntcodeComputer::new("1105,1,0", &[]).unwrap());
+        let b = pipeline.add(IntcodeComputer::new("3,0,99", &[]).unwrap());
+        let c = pipeline.add(IntcodeComputer::new("104,7,1,-1,0,0,99", &[]).unwrap());
+        pipeline.connect(a, b).unwrap();
+        pipeline.connect(c, b).unwrap();
+        let report = pipeline.run();
+        assert_eq!(outcomes(&report)[a], "stopped");
+        assert_eq!(outcomes(&report)[c], "faulted");
+        let (index, err) = report.fault().unwrap();
+        assert_eq!((index, err.pc), (c, 2));
+        assert_eq!(report.links, vec![None, Some(7)]);
+    }
+    #[test]
+    fn test_own_limits() {
+        // A machine with a step limit of its own still stops when another faults
+        let mut pipeline = Pipeline::new();
+        let mut spinner = IntcodeComputer::new("1105,1,0", &[]).unwrap();
+        spinner.set_limits(Limits::steps(u64::MAX));
+        let a = pipeline.add(spinner);
+        let c = pipeline.add(IntcodeComputer::new("1,-1,0,0,99", &[]).unwrap());
+        let report = pipeline.run();
+        assert_eq!(outcomes(&report), vec!["stopped", "faulted"]);
+        assert_eq!(report.machines[a].0.limits, Limits::steps(u64::MAX));
+        assert_eq!(report.fault().unwrap().0, c);
+
+        // A limit longer than a slice is still counted across slices
+        let mut pipeline = Pipeline::new();
+        let mut spinner = IntcodeComputer::new("1105,1,0", &[]).unwrap();
+        spinner.set_limits(Limits::steps(SLICE * 3 + 1));
+        pipeline.add(spinner);
+        let report = pipeline.run();
+        let (index, err) = report.fault().unwrap();
+        assert_eq!(index, 0);
+        assert!(matches!(err.kind, ErrorKind::LimitReached(steps) if steps == SLICE * 3 + 1));
+    }
+    #[test]
+    fn test_bad_link() {
+        let mut pipeline = Pipeline::<Int>::new();
+        let a = pipeline.add(IntcodeComputer::new("99", &[]).unwrap());
+        assert_eq!(pipeline.connect(a, 1), Err(NoSuchMachine(1)));
+        assert_eq!(
+            pipeline.connect(3, a).unwrap_err().to_string(),
+            "no machine 3 in the pipeline"
+        );
+        assert_eq!(pipeline.connect(a, a), Ok(0));
+    }
+    #[test]
+    fn test_send() {
+        fn assert_send<S: Send>() {}
+        assert_send::<IntcodeComputer<Int>>();
+        assert_send::<IntcodeComputer<num_bigint::BigInt>>();
+        assert_send::<PipelineReport>();
+    }
+}
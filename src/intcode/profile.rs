@@ -0,0 +1,98 @@
+use super::OpcodeVariant;
+use std::{collections::HashMap, fmt};
+
+// Rows shown per section of the report
+const REPORT_ROWS: usize = 10;
+
+/// Taken and untaken counts for one conditional jump
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct JumpCounts {
+    pub taken: u64,
+    pub untaken: u64,
+}
+
+/// Execution counts gathered while a machine runs
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Profile {
+    pub total: u64,
+    pub by_variant: HashMap<OpcodeVariant, u64>,
+    pub by_address: HashMap<usize, u64>,
+    /// Keyed by the address of the JumpT/JumpF
+    pub jumps: HashMap<usize, JumpCounts>,
+    /// Taken backward jumps, keyed by (jump address, target) - each one closes a loop
+    pub loops: HashMap<(usize, usize), u64>,
+}
+
+impl Profile {
+    /// Count one instruction.  `jump` is Some(target) for a conditional jump that was taken.
+    pub fn record(&mut self, pc: usize, variant: OpcodeVariant, jump: Option<usize>) {
+        self.total += 1;
+        *self.by_variant.entry(variant).or_insert(0) += 1;
+        *self.by_address.entry(pc).or_insert(0) += 1;
+        if let OpcodeVariant::JumpT | OpcodeVariant::JumpF = variant {
+            let counts = self.jumps.entry(pc).or_default();
+            match jump {
+                Some(target) => {
+                    counts.taken += 1;
+                    if target <= pc {
+                        *self.loops.entry((pc, target)).or_insert(0) += 1;
+                    }
+                }
+                None => counts.untaken += 1,
+            }
+        }
+    }
+    /// Loops sorted hottest first, as ((jump address, target), iterations)
+    pub fn hot_loops(&self) -> Vec<((usize, usize), u64)> {
+        sorted_desc(&self.loops)
+    }
+    /// Addresses sorted by execution count, hottest first
+    pub fn hot_addresses(&self) -> Vec<(usize, u64)> {
+        sorted_desc(&self.by_address)
+    }
+}
+
+/// Highest count first, ties broken by key so reports are stable
+fn sorted_desc<K: Copy + Ord>(counts: &HashMap<K, u64>) -> Vec<(K, u64)> {
+    let mut ret = counts
+        .iter()
+        .map(|(k, v)| (*k, *v))
+        .collect::<Vec<(K, u64)>>();
+    ret.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    ret
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "total instructions: {}", self.total)?;
+        writeln!(f, "by opcode:")?;
+        let mut variants = self
+            .by_variant
+            .iter()
+            .map(|(k, v)| (*k, *v))
+            .collect::<Vec<(OpcodeVariant, u64)>>();
+        variants.sort_by(|a, b| b.1.cmp(&a.1).then((a.0 as u8).cmp(&(b.0 as u8))));
+        for (variant, count) in variants {
+            writeln!(f, "  {:<12} {}", variant.mnemonic(), count)?;
+        }
+        writeln!(f, "hot addresses:")?;
+        for (addr, count) in self.hot_addresses().iter().take(REPORT_ROWS) {
+            writeln!(f, "  {:04}         {}", addr, count)?;
+        }
+        writeln!(f, "hot loops:")?;
+        for ((from, to), count) in self.hot_loops().iter().take(REPORT_ROWS) {
+            writeln!(f, "  {:04} -> {:04} {}", from, to, count)?;
+        }
+        writeln!(f, "jumps (taken/untaken):")?;
+        let mut jumps = self.jumps.iter().collect::<Vec<(&usize, &JumpCounts)>>();
+        jumps.sort_by_key(|(addr, _)| **addr);
+        for (addr, counts) in jumps {
+            writeln!(
+                f,
+                "  {:04}         {}/{}",
+                addr, counts.taken, counts.untaken
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,277 @@
+use super::{Cell, ErrorKind, IntcodeComputer, IntcodeError};
+use std::{
+    ops::RangeInclusive,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+/// Which cells to patch before each run of an input search, and what to try in each
+///
+/// Candidates are tried in order with the last patch varying fastest, so the first match is
+/// the same however many threads are used.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Search {
+    patches: Vec<(usize, RangeInclusive<i64>)>,
+    threads: usize,
+}
+
+impl Default for Search {
+    fn default() -> Self {
+        Self {
+            patches: Vec::new(),
+            threads: thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+        }
+    }
+}
+
+impl Search {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Try every value in `values` at `address`
+    pub fn patch(mut self, address: usize, values: RangeInclusive<i64>) -> Self {
+        self.patches.push((address, values));
+        self
+    }
+    /// Spread candidates over this many worker threads - all available cores by default
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+    /// Number of combinations to try, or None if there are more than a u64 can count
+    pub fn candidates(&self) -> Option<u64> {
+        self.patches
+            .iter()
+            .try_fold(1, |total: u64, (_, r)| total.checked_mul(range_len(r)?))
+    }
+    /// The patch values of the candidate at `index`
+    fn values(&self, mut index: u64) -> Vec<i64> {
+        let mut ret = vec![0; self.patches.len()];
+        for (i, (_, range)) in self.patches.iter().enumerate().rev() {
+            // Only called for searches whose candidates could be counted
+            let len = range_len(range).unwrap();
+            ret[i] = (*range.start()).wrapping_add((index % len) as i64);
+            index /= len;
+        }
+        ret
+    }
+}
+
+/// None only for the full i64 range, which has one value more than a u64 can count
+fn range_len(range: &RangeInclusive<i64>) -> Option<u64> {
+    if range.is_empty() {
+        Some(0)
+    } else {
+        ((*range.end()).wrapping_sub(*range.start()) as u64).checked_add(1)
+    }
+}
+
+impl<T: Cell> IntcodeComputer<T> {
+    /// Every combination of patch values for which `accept` holds once the program has run from
+    /// the current state, in search order.  `accept` sees the finished machine and its outputs.
+    /// Candidates that fault or hit a limit don't match.  Fails if nothing matched, or if there
+    /// are too many candidates to count.
+    pub fn search_all<F>(&self, search: &Search, accept: F) -> Result<Vec<Vec<T>>, IntcodeError<T>>
+    where
+        F: Fn(&IntcodeComputer<T>, &[T]) -> bool + Sync,
+    {
+        let total = self.count_candidates(search)?;
+        let found = self.search(search, total, accept, false);
+        if found.is_empty() {
+            return Err(self.fault(ErrorKind::NoMatch(total), None));
+        }
+        Ok(found)
+    }
+    /// The first combination of patch values in search order for which `accept` holds
+    pub fn search_first<F>(&self, search: &Search, accept: F) -> Result<Vec<T>, IntcodeError<T>>
+    where
+        F: Fn(&IntcodeComputer<T>, &[T]) -> bool + Sync,
+    {
+        let total = self.count_candidates(search)?;
+        self.search(search, total, accept, true)
+            .into_iter()
+            .next()
+            .ok_or_else(|| self.fault(ErrorKind::NoMatch(total), None))
+    }
+    fn count_candidates(&self, search: &Search) -> Result<u64, IntcodeError<T>> {
+        search
+            .candidates()
+            .ok_or_else(|| self.fault(ErrorKind::SearchTooLarge, None))
+    }
+    fn search<F>(&self, search: &Search, total: u64, accept: F, first_only: bool) -> Vec<Vec<T>>
+    where
+        F: Fn(&IntcodeComputer<T>, &[T]) -> bool + Sync,
+    {
+        let checkpoint = self.snapshot();
+        // Workers run the same way as this machine, but without its instrumentation
+        let (engine, overflow, limits) = (self.engine, self.overflow, self.limits);
+        let threads = (search.threads as u64).min(total).max(1);
+        // Lowest matching index so far, so workers can stop once they're past it
+        let best = AtomicU64::new(u64::MAX);
+        let found = Mutex::new(Vec::new());
+        thread::scope(|scope| {
+            for worker in 0..threads {
+                let (checkpoint, accept, best, found) = (&checkpoint, &accept, &best, &found);
+                scope.spawn(move || {
+                    let mut machine = Self::from_snapshot(checkpoint.clone());
+                    machine.engine = engine;
+                    machine.overflow = overflow;
+                    machine.limits = limits;
+                    let mut index = worker;
+                    while index < total {
+                        if first_only && index > best.load(Ordering::Relaxed) {
+                            break;
+                        }
+                        let values = search
+                            .values(index)
+                            .into_iter()
+                            .map(T::from_i64)
+                            .collect::<Vec<T>>();
+                        machine.restore(checkpoint);
+                        for ((address, _), value) in search.patches.iter().zip(&values) {
+                            machine.poke(*address, value.clone());
+                        }
+                        if machine.execute().is_ok() {
+                            let outputs = machine.drain_outputs();
+                            if accept(&machine, &outputs) {
+                                best.fetch_min(index, Ordering::Relaxed);
+                                found.lock().unwrap().push((index, values));
+                                if first_only {
+                                    break;
+                                }
+                            }
+                        }
+                        index = match index.checked_add(threads) {
+                            Some(next) => next,
+                            None => break,
+                        };
+                    }
+                });
+            }
+        });
+        let mut found = found.into_inner().unwrap();
+        found.sort_by_key(|(index, _)| *index);
+        found.into_iter().map(|(_, values)| values).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{Int, IoQueue, Limits};
+    use super::*;
+    use crate::get_puzzle_string;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_values() {
+        let search = Search::new().patch(1, 0..=2).patch(5, -1..=0);
+        assert_eq!(search.candidates(), Some(6));
+        assert_eq!(
+            (0..6).map(|i| search.values(i)).collect::<Vec<_>>(),
+            vec![
+                vec![0, -1],
+                vec![0, 0],
+                vec![1, -1],
+                vec![1, 0],
+                vec![2, -1],
+                vec![2, 0]
+            ]
+        );
+        let (lo, hi) = (3, 2);
+        assert_eq!(Search::new().patch(0, lo..=hi).candidates(), Some(0));
+        assert_eq!(
+            Search::new().patch(0, i64::MIN + 1..=i64::MAX).candidates(),
+            Some(u64::MAX)
+        );
+        // Too many to count, either in one range or across several
+        assert_eq!(
+            Search::new().patch(0, i64::MIN..=i64::MAX).candidates(),
+            None
+        );
+        let wide = Search::new().patch(0, 0..=u32::MAX as i64);
+        assert_eq!(
+            wide.clone().patch(1, 0..=u32::MAX as i64).candidates(),
+            None
+        );
+        assert_eq!(
+            wide.patch(1, 1..=u32::MAX as i64).candidates(),
+            Some(u64::MAX - u32::MAX as u64)
+        );
+    }
+    #[test]
+    fn test_search_outputs() {
+        // Outputs a*b for the two inputs patched into the multiply
+        let computer = IntcodeComputer::new("1102,0,0,7,4,7,99,0", &[]).unwrap();
+        for threads in 1..=4 {
+            let search = Search::new()
+                .patch(1, 1..=12)
+                .patch(2, 1..=12)
+                .threads(threads);
+            let found = computer
+                .search_all(&search, |_, outputs| outputs == [12])
+                .unwrap();
+            assert_eq!(
+                found,
+                vec![
+                    vec![1, 12],
+                    vec![2, 6],
+                    vec![3, 4],
+                    vec![4, 3],
+                    vec![6, 2],
+                    vec![12, 1]
+                ]
+            );
+            assert_eq!(
+                computer
+                    .search_first(&search, |_, outputs| outputs[0] > 100)
+                    .unwrap(),
+                vec![9, 12]
+            );
+        }
+    }
+    #[test]
+    fn test_search_memory() {
+        let day2 = get_puzzle_string(2).unwrap();
+        let computer = IntcodeComputer::new(&day2, &[]).unwrap();
+        let search = Search::new().patch(1, 0..=99).patch(2, 0..=99);
+        assert_eq!(
+            computer
+                .search_first(&search, |m, _| m.result() == 19_690_720)
+                .unwrap(),
+            vec![52, 96]
+        );
+        let err = computer
+            .search_all(&search, |m, _| m.result() == 1)
+            .unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::NoMatch(10_000)));
+        assert_eq!(
+            err.to_string(),
+            "pc 0 (opcode 1): no match among 10000 candidates"
+        );
+    }
+    #[test]
+    fn test_too_large() {
+        let computer = IntcodeComputer::new("99", &[]).unwrap();
+        let search = Search::new().patch(1, i64::MIN..=i64::MAX);
+        let err = computer.search_first(&search, |_, _| true).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::SearchTooLarge));
+        assert!(computer.search_all(&search, |_, _| true).is_err());
+    }
+    #[test]
+    fn test_faults_and_limits() {
+        // Jumps to the patched address - negative ones fault and 0 loops forever
+        let mut computer =
+            IntcodeComputer::<Int>::with_io("1105,1,0,99", IoQueue::default()).unwrap();
+        computer.set_limits(Limits::steps(1000));
+        let search = Search::new().patch(2, -2..=3);
+        assert_eq!(
+            computer.search_all(&search, |_, _| true).unwrap(),
+            vec![vec![3]]
+        );
+    }
+}
@@ -0,0 +1,288 @@
+use super::{Cell, Int, IoQueue, Memory};
+use std::{collections::HashMap, error::Error, fmt, io};
+
+// Bumped whenever the state file layout changes
+const STATE_VERSION: u32 = 1;
+const STATE_HEADER: &str = "intcode-state";
+
+/// A frozen copy of everything an IntcodeComputer needs to pick up where it left off
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Snapshot<T = Int> {
+    pub(super) current_idx: usize,
+    pub(super) relative_base: T,
+    pub(super) memory: Memory<T>,
+    pub(super) io: IoQueue<T>,
+    pub(super) steps: u64,
+}
+
+impl<T: Cell> Snapshot<T> {
+    pub fn pc(&self) -> usize {
+        self.current_idx
+    }
+    pub fn relative_base(&self) -> &T {
+        &self.relative_base
+    }
+    pub fn io(&self) -> &IoQueue<T> {
+        &self.io
+    }
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+}
+
+/// Why a saved state couldn't be read back
+#[derive(Debug)]
+pub enum StateError {
+    Io(io::Error),
+    /// The file is from a newer or unknown format - holds the header line as found
+    Version(String),
+    /// A 1-based line that couldn't be understood
+    Syntax {
+        line: usize,
+        message: String,
+    },
+    /// A required field never appeared
+    Missing(&'static str),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use StateError::*;
+        match self {
+            Io(e) => write!(f, "I/O error: {}", e),
+            Version(header) => write!(
+                f,
+                "unsupported state file {:?}, expected {} v{}",
+                header, STATE_HEADER, STATE_VERSION
+            ),
+            Syntax { line, message } => write!(f, "line {}: {}", line, message),
+            Missing(field) => write!(f, "missing field {}", field),
+        }
+    }
+}
+
+impl Error for StateError {}
+
+impl From<io::Error> for StateError {
+    fn from(e: io::Error) -> Self {
+        StateError::Io(e)
+    }
+}
+
+impl<T: Cell> Snapshot<T> {
+    /// Render as a versioned text file, one `field value` per line.
+    /// Lists are comma-separated and far-out memory is written as `address=value` pairs.
+    pub fn to_text(&self) -> String {
+        let list = |values: &mut dyn Iterator<Item = &T>| {
+            values
+                .map(|v| v.to_string())
+                .collect::<Vec<String>>()
+                .join(",")
+        };
+        let sparse = self
+            .memory
+            .sparse_cells()
+            .map(|(addr, value)| format!("{}={}", addr, value))
+            .collect::<Vec<String>>();
+        format!(
+            "{} v{}\npc {}\nrelative_base {}\nsteps {}\nstdio {}\ninputs {}\noutputs {}\nmemory {}\nsparse {}\n",
+            STATE_HEADER,
+            STATE_VERSION,
+            self.current_idx,
+            self.relative_base,
+            self.steps,
+            self.io.is_stdio(),
+            list(&mut self.io.pending_inputs().iter()),
+            list(&mut self.io.outputs().iter()),
+            self.memory,
+            sparse.join(",")
+        )
+    }
+    /// Read back a snapshot written by `to_text`
+    pub fn from_text(text: &str) -> Result<Self, StateError> {
+        let syntax = |line: usize, message: String| StateError::Syntax { line, message };
+        let mut lines = text.lines().enumerate();
+        let header = lines.next().map(|(_, l)| l.trim()).unwrap_or_default();
+        if header != format!("{} v{}", STATE_HEADER, STATE_VERSION) {
+            return Err(StateError::Version(header.to_string()));
+        }
+        let mut fields = HashMap::new();
+        for (i, line) in lines {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (field, value) = line.split_once(' ').unwrap_or((line, ""));
+            if fields
+                .insert(field.to_string(), (i + 1, value.trim().to_string()))
+                .is_some()
+            {
+                return Err(syntax(i + 1, format!("duplicate field {}", field)));
+            }
+        }
+        let mut field = |name: &'static str| fields.remove(name).ok_or(StateError::Missing(name));
+
+        let (line, pc) = field("pc")?;
+        let current_idx = pc
+            .parse::<usize>()
+            .map_err(|e| syntax(line, format!("bad pc {:?}: {}", pc, e)))?;
+        let (line, rb) = field("relative_base")?;
+        let relative_base =
+            T::parse_cell(&rb).map_err(|e| syntax(line, format!("bad relative base: {}", e)))?;
+        let (line, steps) = field("steps")?;
+        let steps = steps
+            .parse::<u64>()
+            .map_err(|e| syntax(line, format!("bad step count {:?}: {}", steps, e)))?;
+        let (line, stdio) = field("stdio")?;
+        let stdio = stdio
+            .parse::<bool>()
+            .map_err(|e| syntax(line, format!("bad stdio flag {:?}: {}", stdio, e)))?;
+        let mut io = IoQueue::new(&parse_list(field("inputs")?)?);
+        io.set_stdio(stdio);
+        for value in parse_list(field("outputs")?)? {
+            io.push_output(value);
+        }
+        let mut memory = Memory::new(parse_list(field("memory")?)?);
+        let (line, sparse) = field("sparse")?;
+        for pair in sparse.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let bad = |e: String| syntax(line, format!("bad sparse cell {:?}: {}", pair, e));
+            let (addr, value) = pair
+                .split_once('=')
+                .ok_or_else(|| bad("expected ADDRESS=VALUE".to_string()))?;
+            let addr = addr
+                .trim()
+                .parse::<usize>()
+                .map_err(|e| bad(e.to_string()))?;
+            memory.set_cell(addr, T::parse_cell(value.trim()).map_err(bad)?);
+        }
+        if let Some((name, (line, _))) = fields.into_iter().min_by_key(|(_, (line, _))| *line) {
+            return Err(syntax(line, format!("unknown field {}", name)));
+        }
+        Ok(Self {
+            current_idx,
+            relative_base,
+            memory,
+            io,
+            steps,
+        })
+    }
+}
+
+/// A comma-separated field, which may be empty
+fn parse_list<T: Cell>((line, list): (usize, String)) -> Result<Vec<T>, StateError> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(|v| {
+            T::parse_cell(v).map_err(|e| StateError::Syntax {
+                line,
+                message: format!("bad value {:?}: {}", v, e),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::IntcodeComputer;
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// A machine paused on an output, with queued inputs, a moved relative base and a far-out write
+    fn paused() -> IntcodeComputer {
+        let mut computer =
+            IntcodeComputer::new("109,-3,21101,5,6,2000003,4,1,3,7,99", &[]).unwrap();
+        computer.push_input(8);
+        computer.run().unwrap();
+        computer.push_input(9);
+        computer
+    }
+    #[test]
+    fn test_text_roundtrip() {
+        let snapshot = paused().snapshot();
+        let text = snapshot.to_text();
+        assert_eq!(
+            text,
+            [
+                "intcode-state v1",
+                "pc 8",
+                "relative_base -3",
+                "steps 3",
+                "stdio false",
+                "inputs 8,9",
+                "outputs ",
+                "memory 109,-3,21101,5,6,2000003,4,1,3,7,99",
+                "sparse 2000000=11",
+                "",
+            ]
+            .join("\n")
+        );
+        assert_eq!(Snapshot::from_text(&text).unwrap(), snapshot);
+
+        let mut computer = paused();
+        computer.io_mut().push_output(42);
+        let snapshot = computer.snapshot();
+        let restored = Snapshot::<Int>::from_text(&snapshot.to_text()).unwrap();
+        assert_eq!(restored.io().outputs(), snapshot.io().outputs());
+
+        // A stdio machine reloads as one
+        let computer = IntcodeComputer::<Int>::with_io("99", IoQueue::stdio()).unwrap();
+        let text = computer.snapshot().to_text();
+        assert!(text.contains("\nstdio true\n"));
+        assert!(Snapshot::<Int>::from_text(&text).unwrap().io().is_stdio());
+    }
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!("intcode-state-{}.txt", std::process::id()));
+        let mut computer = paused();
+        computer.save_state(&path).unwrap();
+        let mut resumed = IntcodeComputer::load_state(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        computer.execute().unwrap();
+        resumed.execute().unwrap();
+        assert_eq!(resumed.drain_outputs(), computer.drain_outputs());
+        assert_eq!(resumed.snapshot(), computer.snapshot());
+        assert!(matches!(
+            IntcodeComputer::<Int>::load_state(&path),
+            Err(StateError::Io(_))
+        ));
+    }
+    #[test]
+    fn test_errors() {
+        let text = paused().snapshot().to_text();
+        let err = |text: &str| Snapshot::<Int>::from_text(text).unwrap_err().to_string();
+        assert_eq!(
+            err(&text.replace("v1", "v2")),
+            "unsupported state file \"intcode-state v2\", expected intcode-state v1"
+        );
+        assert_eq!(
+            err(&text.replace("pc 8", "pc x")),
+            "line 2: bad pc \"x\": invalid digit found in string"
+        );
+        assert_eq!(
+            err(&text.replace("stdio false", "stdio maybe")),
+            "line 5: bad stdio flag \"maybe\": provided string was not `true` or `false`"
+        );
+        assert_eq!(
+            err(&text.replace("stdio false\n", "")),
+            "missing field stdio"
+        );
+        assert_eq!(
+            err(&text.replace("pc 8\n", "pc 8\npc 9\n")),
+            "line 3: duplicate field pc"
+        );
+        assert_eq!(
+            err(&text.replace("inputs 8,9", "inputs 8,q")),
+            "line 6: bad value \"q\": invalid digit found in string"
+        );
+        assert_eq!(err(&text.replace("steps 3\n", "")), "missing field steps");
+        assert_eq!(
+            err(&format!("{}colour blue\n", text)),
+            "line 10: unknown field colour"
+        );
+        assert_eq!(
+            err(&text.replace("2000000=11", "2000000")),
+            "line 9: bad sparse cell \"2000000\": expected ADDRESS=VALUE"
+        );
+    }
+}
@@ -0,0 +1,342 @@
+use super::{Cell, IntcodeComputer, Memory, OpcodeVariant, ParameterMode, MAX_INPUT};
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::TryFrom,
+    fmt,
+};
+
+// Give up on programs that are still running after this many instructions
+const STEP_LIMIT: usize = 100_000;
+
+/// A polynomial in the noun and verb, as built by symbolic execution
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Expression {
+    /// Coefficients by (noun power, verb power), with no zero coefficients
+    terms: BTreeMap<(u32, u32), i128>,
+}
+
+impl Expression {
+    fn constant(c: i128) -> Self {
+        Self::term((0, 0), c)
+    }
+    fn term(powers: (u32, u32), c: i128) -> Self {
+        let mut terms = BTreeMap::new();
+        if c != 0 {
+            terms.insert(powers, c);
+        }
+        Self { terms }
+    }
+    pub fn noun() -> Self {
+        Self::term((1, 0), 1)
+    }
+    pub fn verb() -> Self {
+        Self::term((0, 1), 1)
+    }
+    /// Some if the expression doesn't depend on the noun or verb
+    pub fn as_constant(&self) -> Option<i128> {
+        match self.terms.iter().next() {
+            None => Some(0),
+            Some((&(0, 0), &c)) if self.terms.len() == 1 => Some(c),
+            _ => None,
+        }
+    }
+    /// None on overflow
+    pub fn add(&self, rhs: &Self) -> Option<Self> {
+        let mut terms = self.terms.clone();
+        for (powers, c) in &rhs.terms {
+            let sum = terms.get(powers).copied().unwrap_or(0).checked_add(*c)?;
+            if sum == 0 {
+                terms.remove(powers);
+            } else {
+                terms.insert(*powers, sum);
+            }
+        }
+        Some(Self { terms })
+    }
+    /// None on overflow
+    pub fn mul(&self, rhs: &Self) -> Option<Self> {
+        let mut ret = Self::default();
+        for ((n1, v1), c1) in &self.terms {
+            for ((n2, v2), c2) in &rhs.terms {
+                ret = ret.add(&Self::term((n1 + n2, v1 + v2), (*c1).checked_mul(*c2)?))?;
+            }
+        }
+        Some(ret)
+    }
+    /// Value at a given noun and verb, or None on overflow
+    pub fn eval(&self, noun: i128, verb: i128) -> Option<i128> {
+        self.terms.iter().try_fold(0i128, |acc, ((n, v), c)| {
+            let term = noun.checked_pow(*n)?.checked_mul(verb.checked_pow(*v)?)?;
+            acc.checked_add((*c).checked_mul(term)?)
+        })
+    }
+    /// The first verb in range giving `target` for this noun
+    fn solve_verb(&self, noun: i128, target: i128) -> Option<i128> {
+        // Coefficients of each power of the verb, once the noun is substituted
+        let mut coefficients = Vec::new();
+        for ((n, v), c) in &self.terms {
+            let v = *v as usize;
+            if v >= coefficients.len() {
+                coefficients.resize(v + 1, 0i128);
+            }
+            coefficients[v] =
+                coefficients[v].checked_add((*c).checked_mul(noun.checked_pow(*n)?)?)?;
+        }
+        match coefficients.as_slice() {
+            [] => (target == 0).then_some(0),
+            [c] => (*c == target).then_some(0),
+            [c, m] => {
+                let diff = target.checked_sub(*c)?;
+                if *m == 0 {
+                    return (diff == 0).then_some(0);
+                }
+                // i128::MIN / -1 overflows, and has no solution in range anyway
+                let verb = diff.checked_div(*m)?;
+                (diff.checked_rem(*m)? == 0 && (0..=MAX_INPUT as i128).contains(&verb))
+                    .then_some(verb)
+            }
+            _ => (0..=MAX_INPUT as i128).find(|v| self.eval(noun, *v) == Some(target)),
+        }
+    }
+    /// The first (noun, verb) in `locate_target` search order giving `target`
+    pub fn solve(&self, target: i128) -> Option<(i64, i64)> {
+        (0..=MAX_INPUT).find_map(|noun| {
+            self.solve_verb(noun as i128, target)
+                .map(|verb| (noun, verb as i64))
+        })
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.terms.is_empty() {
+            return write!(f, "0");
+        }
+        // Highest total power first
+        let mut terms = self.terms.iter().collect::<Vec<_>>();
+        terms.sort_by(|a, b| (b.0 .0 + b.0 .1, b.0).cmp(&(a.0 .0 + a.0 .1, a.0)));
+        for (i, ((n, v), c)) in terms.into_iter().enumerate() {
+            let sign = match (i, *c < 0) {
+                (0, false) => "",
+                (0, true) => "-",
+                (_, false) => " + ",
+                (_, true) => " - ",
+            };
+            let mut factors = Vec::new();
+            if c.abs() != 1 || (*n, *v) == (0, 0) {
+                factors.push(c.abs().to_string());
+            }
+            for (name, power) in &[("noun", *n), ("verb", *v)] {
+                match power {
+                    0 => (),
+                    1 => factors.push(name.to_string()),
+                    p => factors.push(format!("{}^{}", name, p)),
+                }
+            }
+            write!(f, "{}{}", sign, factors.join("*"))?;
+        }
+        Ok(())
+    }
+}
+
+/// A cell's contents during symbolic execution - None once it depends on memory at an
+/// address that depends on the noun or verb
+type Value = Option<Expression>;
+
+struct SymbolicMachine<'a, T> {
+    /// The real machine's memory, read wherever nothing has been written symbolically
+    memory: &'a Memory<T>,
+    written: HashMap<usize, Value>,
+    pc: usize,
+    relative_base: Value,
+}
+
+impl<T: Cell> SymbolicMachine<'_, T> {
+    fn cell(&self, addr: usize) -> Value {
+        match self.written.get(&addr) {
+            Some(value) => value.clone(),
+            None => self
+                .memory
+                .cell(addr)
+                .to_i64()
+                .map(|c| Expression::constant(c as i128)),
+        }
+    }
+    fn constant(&self, addr: usize) -> Option<i128> {
+        self.cell(addr)?.as_constant()
+    }
+    /// The address a parameter refers to, None if it's symbolic or negative, or Err if immediate
+    fn address(&self, value: &Value, mode: ParameterMode) -> Result<Option<usize>, ()> {
+        let addr = match mode {
+            ParameterMode::Position => value.clone(),
+            ParameterMode::Relative => match (&self.relative_base, value) {
+                (Some(rb), Some(v)) => rb.add(v),
+                _ => None,
+            },
+            ParameterMode::Immediate => return Err(()),
+        };
+        Ok(addr
+            .and_then(|a| a.as_constant())
+            .and_then(|a| usize::try_from(a).ok()))
+    }
+    fn read(&self, value: Value, mode: ParameterMode) -> Value {
+        match self.address(&value, mode) {
+            Ok(addr) => self.cell(addr?),
+            Err(()) => value,
+        }
+    }
+    /// None if the destination isn't known, since that could clobber anything
+    fn write(&mut self, value: &Value, mode: ParameterMode, result: Value) -> Option<()> {
+        let addr = self.address(value, mode).ok()??;
+        self.written.insert(addr, result);
+        Some(())
+    }
+    /// Run to Terminate, returning cell 0.  None on anything whose outcome depends on the
+    /// noun or verb, on input, or on anything that would fault.
+    fn run(mut self) -> Option<Expression> {
+        use OpcodeVariant::*;
+        for _ in 0..STEP_LIMIT {
+            let raw = i64::try_from(self.constant(self.pc)?).ok()?;
+            let variant = OpcodeVariant::new(raw)?;
+            let mut modes = raw / 100;
+            let mut params = Vec::new();
+            for i in 1..variant.instruction_len() {
+                let mode = ParameterMode::new(modes % 10)?;
+                params.push((self.cell(self.pc + i), mode));
+                modes /= 10;
+            }
+            let arg = |i: usize| self.read(params[i].0.clone(), params[i].1);
+            let mut next = self.pc + variant.instruction_len();
+            match variant {
+                Add | Multiply | LessThan | Equals => {
+                    let (lhs, rhs) = (arg(0), arg(1));
+                    let result = match (variant, &lhs, &rhs) {
+                        (Add, Some(l), Some(r)) => Some(l.add(r)?),
+                        (Multiply, Some(l), Some(r)) => Some(l.mul(r)?),
+                        (Add, _, _) | (Multiply, _, _) => None,
+                        // Comparisons are only safe on constants
+                        (_, l, r) => {
+                            let (l, r) = (l.as_ref()?.as_constant()?, r.as_ref()?.as_constant()?);
+                            let holds = if variant == LessThan { l < r } else { l == r };
+                            Some(Expression::constant(holds as i128))
+                        }
+                    };
+                    self.write(&params[2].0, params[2].1, result)?;
+                }
+                JumpT | JumpF => {
+                    let check = arg(0)?.as_constant()?;
+                    if (check != 0) == (variant == JumpT) {
+                        next = usize::try_from(arg(1)?.as_constant()?).ok()?;
+                    }
+                }
+                AdjustBase => {
+                    self.relative_base = match (&self.relative_base, arg(0)) {
+                        (Some(rb), Some(offset)) => Some(rb.add(&offset)?),
+                        _ => None,
+                    };
+                }
+                Input => return None,
+                Output => (),
+                Terminate => return self.cell(0),
+            }
+            self.pc = next;
+        }
+        None
+    }
+}
+
+impl<T: Cell> IntcodeComputer<T> {
+    /// Cell 0 at Terminate as an expression in the noun and verb, running from the current
+    /// state with them in cells 1 and 2.  None if the program does anything that depends on
+    /// their values other than arithmetic - a data-dependent jump, say - or reads input.
+    pub fn symbolic_result(&self) -> Option<Expression> {
+        let mut written = HashMap::new();
+        written.insert(1, Some(Expression::noun()));
+        written.insert(2, Some(Expression::verb()));
+        SymbolicMachine {
+            memory: &self.memory,
+            written,
+            pc: self.current_idx,
+            relative_base: self
+                .relative_base
+                .to_i64()
+                .map(|rb| Expression::constant(rb as i128)),
+        }
+        .run()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{Int, IntcodeComputer};
+    use super::*;
+    use crate::get_puzzle_string;
+    use pretty_assertions::assert_eq;
+
+    fn expression(program: &str) -> Option<String> {
+        IntcodeComputer::new(program, &[])
+            .unwrap()
+            .symbolic_result()
+            .map(|e| e.to_string())
+    }
+    #[test]
+    fn test_expressions() {
+        // The first instruction reads cells at the noun and verb but its result is overwritten
+        assert_eq!(expression("1,0,0,0,2,1,2,0,99").unwrap(), "noun*verb");
+        assert_eq!(
+            expression("1,0,0,0,2,2,2,3,1,3,1,0,99").unwrap(),
+            "verb^2 + noun"
+        );
+        assert_eq!(expression("1101,0,0,0,1102,-3,7,0,99").unwrap(), "-21");
+        // Jumping on a value that depends on the noun, writing through it, and input all give up
+        assert_eq!(expression("1005,1,7,1101,0,7,0,99"), None);
+        assert_eq!(expression("1,0,0,0,9,1,21101,7,7,0,99"), None);
+        assert_eq!(expression("3,0,99"), None);
+        // Cells far past the program are read and written like any other
+        assert_eq!(
+            expression("1,0,0,0,1101,0,5,2000000,1,1,2000000,0,99").unwrap(),
+            "noun + 5"
+        );
+        let mut computer = IntcodeComputer::new("1,0,0,0,1,1,2000000,0,99", &[]).unwrap();
+        computer.poke(2_000_000, 7);
+        assert_eq!(computer.symbolic_result().unwrap().to_string(), "noun + 7");
+        // Constant jumps are followed
+        assert_eq!(
+            expression("1,0,0,0,1105,1,9,99,99,1001,1,-4,0,99").unwrap(),
+            "noun - 4"
+        );
+    }
+    #[test]
+    fn test_solve() {
+        let e = Expression::noun()
+            .mul(&Expression::constant(100))
+            .unwrap()
+            .add(&Expression::verb())
+            .unwrap();
+        assert_eq!(e.solve(5296), Some((52, 96)));
+        assert_eq!(e.solve(99_999), None);
+        let square = Expression::verb().mul(&Expression::verb()).unwrap();
+        assert_eq!(
+            square.add(&Expression::noun()).unwrap().solve(13),
+            Some((4, 3))
+        );
+        assert_eq!(Expression::default().solve(0), Some((0, 0)));
+        // i128::MIN / -1 doesn't fit
+        let negated = Expression::verb().mul(&Expression::constant(-1)).unwrap();
+        assert_eq!(negated.solve(i128::MIN), None);
+        assert_eq!(negated.solve(-7), Some((0, 7)));
+        assert_eq!(e.eval(1, 2), Some(102));
+    }
+    #[test]
+    fn test_day2() {
+        let day2 = get_puzzle_string(2).unwrap();
+        let computer = IntcodeComputer::new(&day2, &[]).unwrap();
+        let e = computer.symbolic_result().unwrap();
+        assert_eq!(e.to_string(), "368640*noun + verb + 521344");
+        assert_eq!(e.eval(12, 2), Some(4945026));
+        assert_eq!(e.solve(19_690_720), Some((52, 96)));
+        // The same answer whether solved or searched
+        let mut computer = IntcodeComputer::<Int>::new(&day2, &[]).unwrap();
+        assert_eq!(computer.locate_target(19_690_720).unwrap(), (52, 96));
+    }
+}
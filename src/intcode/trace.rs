@@ -0,0 +1,140 @@
+use super::{Cell, Int, OpcodeVariant};
+use std::{
+    fmt,
+    io::{self, Write},
+};
+
+/// A single memory write made by an instruction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MemWrite<T = Int> {
+    pub address: usize,
+    pub old: T,
+    pub new: T,
+}
+
+/// Everything one executed instruction did, or why it couldn't be executed
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceRecord<T = Int> {
+    pub step: u64,
+    pub pc: usize,
+    pub opcode: T,
+    /// None if the opcode didn't decode
+    pub variant: Option<OpcodeVariant>,
+    /// Values read for each parameter, or the resolved address for a destination parameter
+    pub operands: Vec<T>,
+    pub write: Option<MemWrite<T>>,
+    /// What went wrong, if the instruction faulted
+    pub error: Option<String>,
+}
+
+impl<T: Cell> fmt::Display for TraceRecord<T> {
+    /// One JSON object, no trailing newline.  Only faults have an "error" key.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let operands = self
+            .operands
+            .iter()
+            .map(|o| o.to_string())
+            .collect::<Vec<String>>();
+        let variant = match self.variant {
+            Some(variant) => format!("\"{:?}\"", variant),
+            None => "null".to_string(),
+        };
+        write!(
+            f,
+            r#"{{"step":{},"pc":{},"opcode":{},"variant":{},"operands":[{}],"write":"#,
+            self.step,
+            self.pc,
+            self.opcode,
+            variant,
+            operands.join(",")
+        )?;
+        match &self.write {
+            Some(w) => write!(
+                f,
+                r#"{{"address":{},"old":{},"new":{}}}"#,
+                w.address, w.old, w.new
+            )?,
+            None => write!(f, "null")?,
+        }
+        if let Some(error) = &self.error {
+            write!(f, r#","error":{}"#, json_string(error))?;
+        }
+        write!(f, "}}")
+    }
+}
+
+/// `s` quoted and escaped as a JSON string
+fn json_string(s: &str) -> String {
+    let mut ret = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            c if c.is_control() => ret.push_str(&format!("\\u{:04x}", c as u32)),
+            c => ret.push(c),
+        }
+    }
+    ret.push('"');
+    ret
+}
+
+/// Writes TraceRecords out as JSON lines
+pub struct Tracer {
+    sink: Box<dyn Write + Send>,
+}
+
+impl Tracer {
+    pub fn new<W: Write + Send + 'static>(sink: W) -> Self {
+        Self {
+            sink: Box::new(sink),
+        }
+    }
+    pub fn record<T: Cell>(&mut self, record: &TraceRecord<T>) -> Result<(), io::Error> {
+        writeln!(self.sink, "{}", record)
+    }
+    pub fn flush(&mut self) -> Result<(), io::Error> {
+        self.sink.flush()
+    }
+}
+
+impl fmt::Debug for Tracer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Tracer")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    #[test]
+    fn test_json() {
+        let mut record = TraceRecord::<Int> {
+            step: 3,
+            pc: 4,
+            opcode: 1002,
+            variant: Some(OpcodeVariant::Multiply),
+            operands: vec![33, 3, 4],
+            write: Some(MemWrite {
+                address: 4,
+                old: 33,
+                new: 99,
+            }),
+            error: None,
+        };
+        assert_eq!(
+            record.to_string(),
+            r#"{"step":3,"pc":4,"opcode":1002,"variant":"Multiply","operands":[33,3,4],"write":{"address":4,"old":33,"new":99}}"#
+        );
+        record.write = None;
+        record.operands.clear();
+        assert!(record
+            .to_string()
+            .ends_with(r#""operands":[],"write":null}"#));
+        record.variant = None;
+        record.error = Some("bad \"quote\"\n".to_string());
+        assert!(record.to_string().ends_with(
+            r#""variant":null,"operands":[],"write":null,"error":"bad \"quote\"\u000a"}"#
+        ));
+    }
+}
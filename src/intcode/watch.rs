@@ -0,0 +1,222 @@
+use super::{Cell, Int, MemWrite};
+use std::{collections::HashMap, fmt, ops::Range};
+
+/// Which accesses a watchpoint cares about
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl WatchKind {
+    fn matches<T>(self, access: &Access<T>) -> bool {
+        matches!(
+            (self, access),
+            (WatchKind::ReadWrite, _)
+                | (WatchKind::Read, Access::Read(_))
+                | (WatchKind::Write, Access::Write(_))
+        )
+    }
+}
+
+type WatchCallback<T> = Box<dyn FnMut(&WatchHit<T>) + Send>;
+
+/// What to do when a watchpoint fires
+pub enum WatchAction<T = Int> {
+    /// Finish the instruction, then hand back `RunStatus::Watch` from `run` or `step`
+    Pause,
+    /// Call back and keep going
+    Callback(WatchCallback<T>),
+}
+
+impl<T> WatchAction<T> {
+    pub fn callback<F: FnMut(&WatchHit<T>) + Send + 'static>(f: F) -> Self {
+        WatchAction::Callback(Box::new(f))
+    }
+    fn fire(&mut self, hit: &WatchHit<T>) -> bool {
+        match self {
+            WatchAction::Pause => true,
+            WatchAction::Callback(f) => {
+                f(hit);
+                false
+            }
+        }
+    }
+}
+
+impl<T> fmt::Debug for WatchAction<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WatchAction::Pause => write!(f, "Pause"),
+            WatchAction::Callback(_) => write!(f, "Callback"),
+        }
+    }
+}
+
+/// A data access made by an instruction parameter
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access<T = Int> {
+    Read(T),
+    Write(MemWrite<T>),
+}
+
+/// Why a WatchHit fired
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WatchCause {
+    /// The watchpoint with this id
+    Watchpoint(usize),
+    /// A write landed on a cell that has already executed as part of an instruction
+    SelfModifying,
+}
+
+/// One access that tripped a watchpoint or the self-modification check
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WatchHit<T = Int> {
+    /// Address of the instruction that made the access
+    pub pc: usize,
+    pub step: u64,
+    pub address: usize,
+    pub access: Access<T>,
+    pub cause: WatchCause,
+}
+
+impl<T: Cell> fmt::Display for WatchHit<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.access {
+            Access::Read(value) => write!(f, "read {:04} = {}", self.address, value)?,
+            Access::Write(w) => write!(f, "write {:04}: {} -> {}", w.address, w.old, w.new)?,
+        }
+        write!(f, " by {:04}", self.pc)?;
+        match self.cause {
+            WatchCause::Watchpoint(id) => write!(f, " (watchpoint {})", id),
+            WatchCause::SelfModifying => write!(f, " (self-modifying)"),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Watchpoint<T> {
+    id: usize,
+    range: Range<usize>,
+    kind: WatchKind,
+    action: WatchAction<T>,
+}
+
+/// Every watchpoint set on a machine, plus the self-modification check
+#[derive(Debug)]
+pub(super) struct Watches<T> {
+    points: Vec<Watchpoint<T>>,
+    next_id: usize,
+    self_modification: Option<WatchAction<T>>,
+    /// Every cell that has been part of an executed instruction while the check is on, with the
+    /// step it first executed at
+    executed: HashMap<usize, u64>,
+}
+
+impl<T> Default for Watches<T> {
+    fn default() -> Self {
+        Self {
+            points: Vec::new(),
+            next_id: 0,
+            self_modification: None,
+            executed: HashMap::new(),
+        }
+    }
+}
+
+impl<T: Cell> Watches<T> {
+    pub fn add(&mut self, range: Range<usize>, kind: WatchKind, action: WatchAction<T>) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.points.push(Watchpoint {
+            id,
+            range,
+            kind,
+            action,
+        });
+        id
+    }
+    pub fn remove(&mut self, id: usize) -> bool {
+        let before = self.points.len();
+        self.points.retain(|p| p.id != id);
+        before != self.points.len()
+    }
+    pub fn set_self_modification(&mut self, action: Option<WatchAction<T>>) {
+        if action.is_none() {
+            self.executed.clear();
+        }
+        self.self_modification = action;
+    }
+    /// Forget cells first executed at or after `step`, after stepping back to it
+    pub fn rewind(&mut self, step: u64) {
+        self.executed.retain(|_, first| *first < step);
+    }
+    /// Forget every executed cell, when the machine jumps to another timeline
+    pub fn reset(&mut self) {
+        self.executed.clear();
+    }
+    /// Whether there's anything to check at all - lets the hot path skip the work
+    pub fn is_active(&self) -> bool {
+        !self.points.is_empty() || self.self_modification.is_some()
+    }
+    pub fn watches_reads(&self) -> bool {
+        self.points.iter().any(|p| p.kind != WatchKind::Write)
+    }
+    /// Check one executed instruction's accesses, firing callbacks as they match.
+    /// Returns the first hit whose action is `Pause`.
+    pub fn check(
+        &mut self,
+        pc: usize,
+        len: usize,
+        step: u64,
+        reads: &[(usize, T)],
+        write: Option<&MemWrite<T>>,
+    ) -> Option<WatchHit<T>> {
+        let mut accesses = reads
+            .iter()
+            .map(|(address, value)| (*address, Access::Read(value.clone())))
+            .collect::<Vec<(usize, Access<T>)>>();
+        if let Some(w) = write {
+            accesses.push((w.address, Access::Write(w.clone())));
+        }
+        let mut pause = None;
+        for (address, access) in accesses {
+            for point in &mut self.points {
+                if point.range.contains(&address) && point.kind.matches(&access) {
+                    let hit = WatchHit {
+                        pc,
+                        step,
+                        address,
+                        access: access.clone(),
+                        cause: WatchCause::Watchpoint(point.id),
+                    };
+                    if point.action.fire(&hit) && pause.is_none() {
+                        pause = Some(hit);
+                    }
+                }
+            }
+        }
+        if let Some(action) = self.self_modification.as_mut() {
+            // The instruction itself counts, so one that overwrites itself is caught
+            for addr in pc..pc + len {
+                self.executed.entry(addr).or_insert(step);
+            }
+            if let Some(w) = write {
+                if self.executed.contains_key(&w.address) {
+                    let hit = WatchHit {
+                        pc,
+                        step,
+                        address: w.address,
+                        access: Access::Write(w.clone()),
+                        cause: WatchCause::SelfModifying,
+                    };
+                    if action.fire(&hit) && pause.is_none() {
+                        pause = Some(hit);
+                    }
+                }
+            }
+        }
+        pause
+    }
+}
@@ -8,7 +8,15 @@ pub mod day5;
 pub mod day6;
 pub mod day7;
 
-pub use intcode::{Int, IntcodeComputer};
+pub use intcode::{
+    assemble, control_flow_graph, disassemble, parse_program, Access, AsciiComputer, AsciiOutput,
+    AsmError, BasicBlock, Cell, ControlFlowGraph, Debugger, Edge, Engine, ErrorKind, Expression,
+    History, HistoryEntry, Int, IntcodeComputer, IntcodeError, IoQueue, JumpCounts, Limits, Line,
+    MemWrite, Network, NetworkError, NoSuchMachine, Opcode, OpcodeVariant, Outcome, Overflow,
+    Packet, Parameter, ParameterMode, ParseError, ParseErrorKind, Pipeline, PipelineReport,
+    Profile, RunStatus, Search, Snapshot, StateError, TraceRecord, Tracer, WatchAction, WatchCause,
+    WatchHit, WatchKind, MONITOR_ADDRESS,
+};
 
 use std::{
     fs::File,